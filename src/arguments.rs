@@ -1,11 +1,22 @@
-use std::{fmt::Display, num::NonZeroUsize};
+use std::{ffi::OsString, fmt::Display, num::NonZeroUsize, path::PathBuf, time::Duration};
 
-use clap::{builder::OsStr, Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap::{
+    builder::OsStr, parser::ValueSource, ArgAction, ArgGroup, Args, FromArgMatches, Parser,
+    Subcommand, ValueEnum,
+};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 #[derive(Parser)]
 #[clap(version)]
 pub struct Arguments {
+    #[clap(
+        long = "config",
+        value_name = "FILE",
+        global = true,
+        help = "Configuration file [default: ~/.config/grd/config.toml]"
+    )]
+    pub config: Option<PathBuf>,
     #[clap(subcommand)]
     pub command_mode: CommandMode,
 }
@@ -13,41 +24,457 @@ pub struct Arguments {
 #[derive(Subcommand)]
 pub enum CommandMode {
     #[clap(about = "Download an asset")]
-    Download(DownloadArgs),
+    Download(Box<DownloadArgs>),
     #[clap(about = "Query information about assets or releases of a repository")]
-    Query(QueryArgs),
+    Query(Box<QueryArgs>),
+    #[clap(about = "Install an executable of a release asset")]
+    Install(Box<InstallArgs>),
+    #[clap(about = "List the tools installed with grd")]
+    List(ListArgs),
+    #[clap(about = "Update installed tools to their latest release")]
+    Update(Box<UpdateArgs>),
+    #[clap(about = "Remove an installed tool")]
+    Uninstall(UninstallArgs),
+    #[clap(about = "Remove old versions of installed tools and cached downloads")]
+    Gc(GcArgs),
+    #[clap(about = "Switch an installed tool back to its previous version")]
+    Rollback(RollbackArgs),
+    #[clap(about = "Switch an installed tool to an installed version and keep it on updates")]
+    Use(UseArgs),
+    #[clap(about = "Install, update and remove tools to match a manifest (grd.toml)")]
+    Sync(Box<SyncArgs>),
+    #[clap(about = "Resolve the tools of a manifest and record them in its lockfile (grd.lock)")]
+    Lock(LockArgs),
 }
 
+/// Selects the asset of a release, shared by all commands downloading an asset.
 #[derive(Args)]
-pub struct DownloadArgs {
+pub struct AssetArgs {
     #[clap(flatten)]
     pub repository: Repository,
     #[clap(
-        help = "Regex pattern of the asset to download\nIf pattern matches multiple assets, the first matching will be downloaded"
+        help = "Regex pattern of the asset to download\nIf pattern matches multiple assets, the first matching will be downloaded\nMay be omitted if the repository is an alias with a stored pattern"
     )]
     pub asset_pattern: String,
 
     #[clap(
         short = 't',
         long = "tag",
+        env = "GRD_TAG",
         help = "Tag of the release (latest if omitted)"
     )]
     pub tag: Option<String>,
 
+    #[clap(
+        long = "version",
+        env = "GRD_VERSION",
+        value_name = "REQUIREMENT",
+        conflicts_with = "tag",
+        help = "Semantic version requirement like \">=1.4, <2\", \"~1.4\" or \"^1.4\"\nThe release with the highest matching version is used, tags which are not versions are ignored"
+    )]
+    pub version_requirement: Option<String>,
+    #[clap(
+        long = "tag-prefix",
+        env = "GRD_TAG_PREFIX",
+        value_name = "PREFIX",
+        requires = "version_requirement",
+        help = "Prefix of the tags in front of the version, e.g. \"release-\" (a \"v\" is always accepted)"
+    )]
+    pub tag_prefix: Option<String>,
+    #[clap(
+        long = "tag-pattern",
+        env = "GRD_TAG_PATTERN",
+        value_name = "REGEX",
+        conflicts_with = "tag",
+        help = "Only use releases whose tag matches the regex pattern\nIf it has a capture group, the version in it is used to find the latest release"
+    )]
+    pub tag_pattern: Option<String>,
+
     #[clap(
         short = 'p',
         long = "prerelease",
+        env = "GRD_PRERELEASE",
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
         default_value_t = false,
-        help = "Include prereleases"
+        default_missing_value = "true",
+        help = "Include prereleases, --prerelease=false overrides the configuration"
     )]
     pub allow_prerelease: bool,
+}
+
+#[derive(Args)]
+pub struct DownloadArgs {
+    #[clap(flatten)]
+    pub asset: AssetArgs,
+    #[clap(
+        short = 'o',
+        long = "output-dir",
+        env = "GRD_OUTPUT_DIR",
+        value_name = "DIR",
+        help = "Directory to download the asset to [default: current directory]"
+    )]
+    pub output_dir: Option<PathBuf>,
     #[clap(
         short = 'f',
         long = "print-filename",
+        env = "GRD_PRINT_FILENAME",
         default_value_t = false,
         help = "Print downloaded filename to stdout"
     )]
     pub print_filename: bool,
+    #[clap(flatten)]
+    pub verify: VerifyArgs,
+    // Some(None) if the flag is passed without a value
+    #[clap(
+        long = "extract",
+        env = "GRD_EXTRACT",
+        value_name = "DIR",
+        help = "Extract the downloaded archive (tar, tar.gz, tar.xz, tar.bz2, tar.zst, zip, deb or rpm) and remove it afterwards\nExtracts into the current directory, unless a directory is passed"
+    )]
+    pub extract: Option<Option<PathBuf>>,
+    #[clap(
+        long = "strip-components",
+        env = "GRD_STRIP_COMPONENTS",
+        value_name = "N",
+        default_value_t = 0,
+        requires = "extract",
+        help = "Remove the first N leading components from the paths of the extracted entries"
+    )]
+    pub strip_components: usize,
+    #[clap(
+        long = "include",
+        env = "GRD_INCLUDE",
+        value_delimiter = '\n',
+        value_name = "GLOB",
+        requires = "extract",
+        help = "Only extract entries whose path (after stripping) matches the glob, can be specified multiple times"
+    )]
+    pub include: Vec<String>,
+    #[clap(
+        long = "stream",
+        env = "GRD_STREAM",
+        default_value_t = false,
+        requires = "extract",
+        conflicts_with_all = ["minisign_key", "pgp_keyring", "sigstore_trust_root"],
+        help = "Extract the archive while downloading it, without writing it to disk\nNot possible for zip archives and signatures, which need the whole file"
+    )]
+    pub stream: bool,
+    #[clap(
+        long = "decompress",
+        env = "GRD_DECOMPRESS",
+        default_value_t = false,
+        conflicts_with = "extract",
+        help = "Decompress a single compressed file (gz, xz, bz2 or zst) and remove the compressed one\nThe compression is detected from the file extension, which is removed, or the content"
+    )]
+    pub decompress: bool,
+    #[clap(
+        long = "executable",
+        env = "GRD_EXECUTABLE",
+        default_value_t = false,
+        conflicts_with = "extract",
+        help = "Mark the downloaded (or decompressed) file as executable"
+    )]
+    pub executable: bool,
+}
+
+#[derive(Args)]
+pub struct InstallArgs {
+    #[clap(flatten)]
+    pub asset: AssetArgs,
+    #[clap(flatten)]
+    pub verify: VerifyArgs,
+    #[clap(
+        long = "bin-dir",
+        value_name = "DIR",
+        help = "Directory to install the executable to [default: ~/.local/bin]"
+    )]
+    pub bin_dir: Option<PathBuf>,
+    #[clap(
+        long = "tools-dir",
+        value_name = "DIR",
+        help = "Directory the versions of installed tools are kept in [default: ~/.local/share/grd/tools]"
+    )]
+    pub tools_dir: Option<PathBuf>,
+    #[clap(
+        long = "name",
+        value_name = "NAME",
+        help = "Install the executable under this name"
+    )]
+    pub name: Option<String>,
+    #[clap(
+        long = "binary",
+        value_name = "GLOB",
+        help = "Glob of the executable inside the archive, if it can not be determined automatically"
+    )]
+    pub binary: Option<String>,
+    #[clap(flatten)]
+    pub state: StateArgs,
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    #[clap(
+        short = 'm',
+        long = "manifest",
+        value_name = "FILE",
+        default_value = crate::manifest::DEFAULT_MANIFEST,
+        help = "Manifest describing the tools to install"
+    )]
+    pub manifest: PathBuf,
+    #[clap(
+        short = 'n',
+        long = "dry-run",
+        default_value_t = false,
+        help = "Only show which tools would be installed, switched or removed"
+    )]
+    pub dry_run: bool,
+    #[clap(
+        long = "locked",
+        default_value_t = false,
+        help = "Install exactly the assets recorded in the lockfile, fail if their hashes changed"
+    )]
+    pub locked: bool,
+    #[clap(
+        long = "force",
+        default_value_t = false,
+        help = "Also remove tools missing from the manifest whose files have been modified"
+    )]
+    pub force: bool,
+    #[clap(
+        long = "bin-dir",
+        value_name = "DIR",
+        help = "Directory to install the executables to, overrides the manifest [default: ~/.local/bin]"
+    )]
+    pub bin_dir: Option<PathBuf>,
+    #[clap(
+        long = "tools-dir",
+        value_name = "DIR",
+        help = "Directory the versions of installed tools are kept in, overrides the manifest [default: ~/.local/share/grd/tools]"
+    )]
+    pub tools_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub state: StateArgs,
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+#[derive(Args)]
+pub struct LockArgs {
+    #[clap(
+        short = 'm',
+        long = "manifest",
+        value_name = "FILE",
+        default_value = crate::manifest::DEFAULT_MANIFEST,
+        help = "Manifest describing the tools to lock, the lockfile is written next to it"
+    )]
+    pub manifest: PathBuf,
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+/// Location of the receipts of installed tools, shared by all commands managing them.
+#[derive(Args)]
+pub struct StateArgs {
+    #[clap(
+        long = "state-dir",
+        value_name = "DIR",
+        help = "Directory the receipts of installed tools are stored in [default: ~/.local/state/grd]"
+    )]
+    pub state_dir: Option<PathBuf>,
+}
+
+/// Location of the downloads cached for installations.
+#[derive(Args)]
+pub struct CacheArgs {
+    #[clap(
+        long = "cache-dir",
+        value_name = "DIR",
+        help = "Directory downloaded assets are cached in [default: ~/.cache/grd]"
+    )]
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("policy")
+        .required(true)
+        .multiple(true)
+        .args(["keep", "cache_max_age", "cache_max_size"])
+))]
+pub struct GcArgs {
+    #[clap(
+        long = "keep",
+        value_name = "N",
+        help = "Keep the N most recently installed versions of every tool (and the active one)"
+    )]
+    pub keep: Option<usize>,
+    #[clap(
+        long = "cache-max-age",
+        value_name = "DURATION",
+        value_parser = crate::install::parse_duration,
+        help = "Remove cached downloads not used for longer, e.g. 30d (units: s, m, h, d, w)"
+    )]
+    pub cache_max_age: Option<Duration>,
+    #[clap(
+        long = "cache-max-size",
+        value_name = "SIZE",
+        value_parser = crate::install::parse_size,
+        help = "Remove the cached downloads used longest ago until the cache is not larger, e.g. 500M"
+    )]
+    pub cache_max_size: Option<u64>,
+    #[clap(
+        short = 'n',
+        long = "dry-run",
+        default_value_t = false,
+        help = "Only show what would be removed"
+    )]
+    pub dry_run: bool,
+    #[clap(flatten)]
+    pub state: StateArgs,
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+#[derive(Args)]
+pub struct UpdateArgs {
+    #[clap(help = "Names of the tools to update (all if omitted)")]
+    pub names: Vec<String>,
+    #[clap(
+        short = 'n',
+        long = "dry-run",
+        default_value_t = false,
+        help = "Only show which tools have a newer release"
+    )]
+    pub dry_run: bool,
+    #[clap(flatten)]
+    pub verify: VerifyArgs,
+    #[clap(flatten)]
+    pub state: StateArgs,
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+#[derive(Args)]
+pub struct UninstallArgs {
+    #[clap(help = "Name of the tool")]
+    pub name: String,
+    #[clap(
+        long = "force",
+        default_value_t = false,
+        help = "Also remove files that have been modified since the installation"
+    )]
+    pub force: bool,
+    #[clap(flatten)]
+    pub state: StateArgs,
+}
+
+#[derive(Args)]
+pub struct RollbackArgs {
+    #[clap(help = "Name of the tool")]
+    pub name: String,
+    #[clap(flatten)]
+    pub state: StateArgs,
+}
+
+#[derive(Args)]
+pub struct UseArgs {
+    #[clap(help = "Name of the tool")]
+    pub name: String,
+    #[clap(help = "Tag of the installed version to use")]
+    pub tag: String,
+    #[clap(flatten)]
+    pub state: StateArgs,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    #[clap(flatten)]
+    pub state: StateArgs,
+    #[clap(
+        long = "json",
+        default_value_t = false,
+        help = "Print the receipts as JSON"
+    )]
+    pub json: bool,
+}
+
+/// Options for verifying downloads, shared by all commands downloading assets.
+#[derive(Args)]
+pub struct VerifyArgs {
+    #[clap(
+        long = "no-verify",
+        env = "GRD_NO_VERIFY",
+        default_value_t = false,
+        help = "Do not verify the download against the digest published by the website"
+    )]
+    pub no_verify: bool,
+    // Some(None) if the flag is passed without a value
+    #[clap(
+        long = "verify-checksums",
+        env = "GRD_VERIFY_CHECKSUMS",
+        value_name = "CHECKSUMS_PATTERN",
+        help = "Verify the download against a checksum file of the release\nThe checksum file is detected automatically, unless a regex pattern for it is passed"
+    )]
+    pub verify_checksums: Option<Option<String>>,
+    #[clap(
+        long = "minisign-key",
+        env = "GRD_MINISIGN_KEY",
+        value_name = "PUBLIC_KEY_OR_FILE",
+        help = "Verify the download against the minisign (or signify) signature of the release\nTakes the base64 encoded public key or a file containing it"
+    )]
+    pub minisign_key: Option<String>,
+    #[clap(
+        long = "pgp-keyring",
+        env = "GRD_PGP_KEYRING",
+        value_name = "FILE",
+        help = "Verify the download against the detached OpenPGP signature of the release\nTakes a file containing the (armored or binary) public keys to trust"
+    )]
+    pub pgp_keyring: Option<PathBuf>,
+    #[clap(
+        long = "pgp-fingerprint",
+        env = "GRD_PGP_FINGERPRINT",
+        value_delimiter = '\n',
+        value_name = "FINGERPRINT",
+        requires = "pgp_keyring",
+        help = "Only trust the key of the keyring with this fingerprint, can be specified multiple times"
+    )]
+    pub pgp_fingerprints: Vec<String>,
+    #[clap(
+        long = "sigstore-trust-root",
+        env = "GRD_SIGSTORE_TRUST_ROOT",
+        value_name = "FILE",
+        help = "Verify the download against the sigstore bundle of the release\nTakes a sigstore trusted_root.json"
+    )]
+    pub sigstore_trust_root: Option<PathBuf>,
+    #[clap(
+        long = "sigstore-identity",
+        env = "GRD_SIGSTORE_IDENTITY",
+        value_name = "REGEX",
+        requires = "sigstore_trust_root",
+        help = "Regex the certificate identity (e.g. the workflow URI) has to match completely"
+    )]
+    pub sigstore_identity: Option<String>,
+    #[clap(
+        long = "sigstore-issuer",
+        env = "GRD_SIGSTORE_ISSUER",
+        value_name = "URL",
+        requires = "sigstore_trust_root",
+        help = "OIDC issuer the certificate has to be issued for"
+    )]
+    pub sigstore_issuer: Option<String>,
+    #[clap(
+        long = "sigstore-bundle",
+        env = "GRD_SIGSTORE_BUNDLE",
+        value_name = "BUNDLE_PATTERN",
+        requires = "sigstore_trust_root",
+        help = "Regex of the sigstore bundle asset, if it can not be guessed from the asset name"
+    )]
+    pub sigstore_bundle: Option<String>,
 }
 
 #[derive(Args)]
@@ -65,6 +492,72 @@ pub enum QueryType {
 }
 
 // the validity of the regex patterns are ensured via unit test
+/// Ignores the environment variables of the arguments conflicting with an
+/// argument passed on the command line, e.g. `--tag` replaces `$GRD_VERSION`
+/// instead of conflicting with it.
+pub fn ignore_conflicting_env(command: clap::Command, args: &[OsString]) -> clap::Command {
+    // required arguments may be missing, so errors are ignored to find the passed ones
+    let Ok(mut matches) = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(args)
+    else {
+        return command;
+    };
+    let mut subcommand_path = Vec::new();
+    let mut subcommand = command.clone();
+    while let Some((name, subcommand_matches)) = matches.remove_subcommand() {
+        let Some(next) = subcommand.find_subcommand(&name).cloned() else {
+            return command;
+        };
+        subcommand_path.push(name);
+        subcommand = next;
+        matches = subcommand_matches;
+    }
+    let source = |arg: &clap::Arg| matches.value_source(arg.get_id().as_str());
+    let conflicts = |arg: &clap::Arg, other: &clap::Arg| {
+        !arg.is_global_set()
+            && subcommand
+                .get_arg_conflicts_with(arg)
+                .iter()
+                .any(|conflict| conflict.get_id() == other.get_id())
+    };
+    let ignored: Vec<clap::Id> = subcommand
+        .get_arguments()
+        .filter(|arg| source(arg) == Some(ValueSource::EnvVariable))
+        .filter(|arg| {
+            subcommand.get_arguments().any(|other| {
+                source(other) == Some(ValueSource::CommandLine)
+                    && (conflicts(arg, other) || conflicts(other, arg))
+            })
+        })
+        .map(|arg| arg.get_id().clone())
+        .collect();
+    if ignored.is_empty() {
+        return command;
+    }
+    mut_subcommand_at(command, &subcommand_path, &|subcommand| {
+        subcommand.mut_args(|arg| match ignored.contains(arg.get_id()) {
+            true => arg.env(None),
+            false => arg,
+        })
+    })
+}
+
+/// Modifies the subcommand at `path` of the command.
+pub(crate) fn mut_subcommand_at(
+    command: clap::Command,
+    path: &[String],
+    f: &dyn Fn(clap::Command) -> clap::Command,
+) -> clap::Command {
+    match path.split_first() {
+        Some((name, path)) => {
+            command.mut_subcommand(name, |subcommand| mut_subcommand_at(subcommand, path, f))
+        }
+        None => f(command),
+    }
+}
+
 fn get_github_optional_origin_and_repository_regex() -> Regex {
     // clippy actually checks for valid regex
     // however it is not enforced on compilation
@@ -85,23 +578,34 @@ fn get_gitlab_origin_sub_path_and_repository_regex() -> Regex {
     get_gitea_origin_sub_path_and_repository_regex()
 }
 
-#[cfg_attr(test, derive(Debug))]
-enum ParseRepositoryError {
+#[derive(Debug)]
+pub enum ParseRepositoryError {
     InvalidRepository(String),
+    /// No website type was given and it could not be guessed from the repository.
+    UnknownWebsiteType(String),
 }
 
+impl std::error::Error for ParseRepositoryError {}
+
 impl Display for ParseRepositoryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseRepositoryError::InvalidRepository(repository_string) => {
                 write!(f, "invalid repository: {}", repository_string)
             }
+            ParseRepositoryError::UnknownWebsiteType(repository_string) => {
+                write!(
+                    f,
+                    "failed to guess website type of \"{}\", it has to be set explicitly",
+                    repository_string
+                )
+            }
         }
     }
 }
 
 // this function takes its arguments as owned values, because they will be moved into the Repository struct
-fn parse_repository(
+pub fn parse_repository(
     repository_string: String,
     website_type: GitWebsite,
     ip_type: IpType,
@@ -124,6 +628,8 @@ fn parse_repository(
                     passed_string: repository_string,
                     ip_type,
                     headers,
+                    proxy: None,
+                    ca_bundle: None,
                 });
             }
         }
@@ -140,6 +646,8 @@ fn parse_repository(
                     passed_string: repository_string,
                     ip_type,
                     headers,
+                    proxy: None,
+                    ca_bundle: None,
                 });
             }
         }
@@ -156,6 +664,8 @@ fn parse_repository(
                     passed_string: repository_string,
                     ip_type,
                     headers,
+                    proxy: None,
+                    ca_bundle: None,
                 });
             }
         }
@@ -170,18 +680,55 @@ pub struct ReleasesQueryArgs {
     #[clap(
         short = 'p',
         long = "prerelease",
+        env = "GRD_PRERELEASE",
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
         default_value_t = false,
-        help = "Include prereleases"
+        default_missing_value = "true",
+        help = "Include prereleases, --prerelease=false overrides the configuration"
     )]
     pub allow_prerelease: bool,
+    #[clap(
+        long = "version",
+        env = "GRD_VERSION",
+        value_name = "REQUIREMENT",
+        help = "Only show releases matching the semantic version requirement, highest version first"
+    )]
+    pub version_requirement: Option<String>,
+    #[clap(
+        long = "tag-prefix",
+        env = "GRD_TAG_PREFIX",
+        value_name = "PREFIX",
+        requires = "version_requirement",
+        help = "Prefix of the tags in front of the version, e.g. \"release-\" (a \"v\" is always accepted)"
+    )]
+    pub tag_prefix: Option<String>,
+    #[clap(
+        long = "tag-pattern",
+        env = "GRD_TAG_PATTERN",
+        value_name = "REGEX",
+        help = "Only show releases whose tag matches the regex pattern\nIf it has a capture group, the releases are sorted by the version in it"
+    )]
+    pub tag_pattern: Option<String>,
     // by default one -> just latest
     #[clap(
         short = 'c',
         long = "count",
+        env = "GRD_COUNT",
         default_value = "1",
         help = "The last n releases to show"
     )]
     pub count: NonZeroUsize,
+    #[clap(
+        short = 'l',
+        long = "long",
+        env = "GRD_LONG",
+        default_value_t = false,
+        help = "Also show name, publication date and whether it is a prerelease"
+    )]
+    pub long: bool,
 }
 
 #[derive(Args)]
@@ -191,31 +738,79 @@ pub struct AssetsQueryArgs {
     #[clap(
         short = 't',
         long = "tag",
+        env = "GRD_TAG",
         help = "Tag of the release\nIf omitted latest (non prerelease) tag will be used"
     )]
     pub tag: Option<String>,
+    #[clap(
+        long = "version",
+        env = "GRD_VERSION",
+        value_name = "REQUIREMENT",
+        conflicts_with = "tag",
+        help = "Semantic version requirement like \">=1.4, <2\", \"~1.4\" or \"^1.4\"\nThe release with the highest matching version is used, tags which are not versions are ignored"
+    )]
+    pub version_requirement: Option<String>,
+    #[clap(
+        long = "tag-prefix",
+        env = "GRD_TAG_PREFIX",
+        value_name = "PREFIX",
+        requires = "version_requirement",
+        help = "Prefix of the tags in front of the version, e.g. \"release-\" (a \"v\" is always accepted)"
+    )]
+    pub tag_prefix: Option<String>,
+    #[clap(
+        long = "tag-pattern",
+        env = "GRD_TAG_PATTERN",
+        value_name = "REGEX",
+        conflicts_with = "tag",
+        help = "Only use releases whose tag matches the regex pattern\nIf it has a capture group, the version in it is used to find the latest release"
+    )]
+    pub tag_pattern: Option<String>,
+    #[clap(
+        short = 'p',
+        long = "prerelease",
+        env = "GRD_PRERELEASE",
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "Include prereleases, --prerelease=false overrides the configuration"
+    )]
+    pub allow_prerelease: bool,
     // ".*" means all assets
     #[clap(
         short = 'a',
         long = "asset-pattern",
+        env = "GRD_ASSET_PATTERN",
         default_value = ".*",
         help = "Asset regex pattern to match against\nIf not supplied all assets will be shown"
     )]
     pub pattern: String,
+    #[clap(
+        short = 'l',
+        long = "long",
+        env = "GRD_LONG",
+        default_value_t = false,
+        help = "Also show size, download count and content type"
+    )]
+    pub long: bool,
 }
 
-#[derive(ValueEnum, Clone)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(ValueEnum, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[clap(rename_all = "lower")]
+#[serde(rename_all = "lowercase")]
 pub enum GitWebsite {
     GitHub,
     Gitea,
     GitLab,
 }
 
-#[derive(ValueEnum, Clone, Copy)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(ValueEnum, Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
 #[clap(rename_all = "lower")]
+#[serde(rename_all = "lowercase")]
 pub enum IpType {
     Any,
     IPV4,
@@ -241,12 +836,13 @@ struct RepositoryArguments {
     // if website type and maybe sub path (depending on the website type) are specified
     // this does not need to be the full url
     #[clap(
-        help = "Repository url (scheme defaults to \"https\" unless explicitly set to \"http\" with \"http://\")"
+        help = "Repository url (scheme defaults to \"https\" unless explicitly set to \"http\" with \"http://\")\nor the name of an alias of the configuration"
     )]
     pub repository: String,
     #[clap(
         short = 'w',
         long = "website-type",
+        env = "GRD_WEBSITE_TYPE",
         ignore_case = true,
         help = "If omitted, it will be guessed from repository url"
     )]
@@ -254,19 +850,22 @@ struct RepositoryArguments {
     #[clap(
         short = 'i',
         long = "ip-type",
+        env = "GRD_IP_TYPE",
         ignore_case = true,
-        help = "IP address type to use",
-        default_value = IpType::Any,
+        help = "IP address type to use [default: any]"
     )]
-    pub ip_type: IpType,
+    pub ip_type: Option<IpType>,
     #[clap(
         long = "header",
+        env = "GRD_HEADER",
+        value_delimiter = '\n',
         help = "Http header to use, can be specified multiple times"
     )]
     pub headers: Vec<String>,
 }
 
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
 pub struct Repository {
     pub website: GitWebsite,
     pub owner: String,
@@ -277,6 +876,21 @@ pub struct Repository {
     pub passed_string: String,
     pub ip_type: IpType,
     pub headers: Vec<String>,
+    /// Url of the proxy requests are sent through, e.g. "http://proxy:3128".
+    pub proxy: Option<String>,
+    /// PEM file of certificate authorities trusted in addition to the built-in ones.
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl Repository {
+    /// The repository in a form [`parse_repository`] accepts for its website,
+    /// independent of how it has been passed.
+    pub fn canonical_string(&self) -> String {
+        format!(
+            "{}{}{}/{}",
+            self.origin, self.sub_path, self.owner, self.name
+        )
+    }
 }
 
 impl FromArgMatches for Repository {
@@ -339,7 +953,10 @@ enum RepositoryArgumentsToRepositoryError {
 
 impl From<ParseRepositoryError> for RepositoryArgumentsToRepositoryError {
     fn from(val: ParseRepositoryError) -> Self {
-        Self::ParseRepository(val)
+        match val {
+            ParseRepositoryError::UnknownWebsiteType(_) => Self::GuessWebsiteFail,
+            val => Self::ParseRepository(val),
+        }
     }
 }
 
@@ -370,22 +987,34 @@ impl TryFrom<RepositoryArguments> for Repository {
             headers,
         } = val;
 
-        // first we check if the website type has been provided as an argument
-        // if not we try to guess it from the passed repository
-        let website_type = match website_type {
-            Some(website_type) => Some(website_type),
-            None => guess_website_type(&repository),
-        };
-
-        // if it could not be guessed we return an error
-        let website_type =
-            website_type.ok_or(RepositoryArgumentsToRepositoryError::GuessWebsiteFail)?;
-
-        let repository = parse_repository(repository, website_type, ip_type, headers)?;
+        let repository = resolve_repository(
+            repository,
+            website_type,
+            ip_type.unwrap_or(IpType::Any),
+            headers,
+        )?;
         Ok(repository)
     }
 }
 
+/// Parses a repository the way the repository arguments are parsed:
+/// if `website_type` is `None`, it is guessed from `repository_string`.
+pub fn resolve_repository(
+    repository_string: String,
+    website_type: Option<GitWebsite>,
+    ip_type: IpType,
+    headers: Vec<String>,
+) -> Result<Repository, ParseRepositoryError> {
+    // first we check if the website type has been provided
+    // if not we try to guess it from the passed repository
+    let website_type = match website_type {
+        Some(website_type) => website_type,
+        None => guess_website_type(&repository_string)
+            .ok_or_else(|| ParseRepositoryError::UnknownWebsiteType(repository_string.clone()))?,
+    };
+    parse_repository(repository_string, website_type, ip_type, headers)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,6 +1049,8 @@ mod tests {
             passed_string: "https://github.com/cm-auto/gitweb-release-downloader".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -442,6 +1073,8 @@ mod tests {
             passed_string: "github.com/cm-auto/gitweb-release-downloader".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -464,6 +1097,8 @@ mod tests {
             passed_string: "cm-auto/gitweb-release-downloader".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -486,6 +1121,8 @@ mod tests {
             passed_string: "https://codeberg.org/forgejo/forgejo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -508,6 +1145,8 @@ mod tests {
             passed_string: "codeberg.org/forgejo/forgejo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -530,6 +1169,8 @@ mod tests {
             passed_string: "https://gitea.example.com/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -552,6 +1193,8 @@ mod tests {
             passed_string: "gitea.example.com/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -574,6 +1217,8 @@ mod tests {
             passed_string: "https://example.com/gitea/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -596,6 +1241,8 @@ mod tests {
             passed_string: "example.com/gitea/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -618,6 +1265,8 @@ mod tests {
             passed_string: "https://example.com:1337/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
@@ -640,6 +1289,8 @@ mod tests {
             passed_string: "example.com:1337/owner/repo".to_string(),
             ip_type: IpType::Any,
             headers: vec![],
+            proxy: None,
+            ca_bundle: None,
         };
         assert_eq!(repository, expected);
     }
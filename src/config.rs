@@ -0,0 +1,655 @@
+//! The configuration file, `~/.config/grd/config.toml` (or passed with
+//! `--config`), holds defaults for the command line options and settings per
+//! host:
+//!
+//! ```toml
+//! prerelease = false
+//! output-dir = "~/Downloads"
+//!
+//! [hosts."codeberg.org"]
+//! website-type = "gitea"
+//! ip-type = "ipv4"
+//! headers = ["X-Client: grd"]
+//! token = { env = "CODEBERG_TOKEN" }
+//!
+//! [hosts."git.example.com"]
+//! website-type = "gitlab"
+//! token = { command = "pass show git.example.com/token" }
+//! ca-bundle = "/etc/ssl/certs/example-ca.pem"
+//! proxy = "http://proxy.example.com:3128"
+//!
+//! [aliases]
+//! vscodium = { repo = "github.com/VSCodium/vscodium", pattern = "\\.deb$" }
+//! ```
+//!
+//! Options passed on the command line take precedence over the configuration.
+
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use clap::{builder::ValueParser, parser::ValueSource, ValueEnum};
+use serde::Deserialize;
+
+use crate::{
+    arguments::mut_subcommand_at, resolve_repository, Error, GitWebsite, IpType, Repository,
+};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The file the configuration has been read from.
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Whether prereleases are included by default.
+    pub prerelease: Option<bool>,
+    /// Directory assets are downloaded to by default, instead of the current directory.
+    pub output_dir: Option<PathBuf>,
+    /// Settings of the hosts, keyed by the host (and port) of the repository url.
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostConfig>,
+    /// Names which can be passed instead of a repository, keyed by the name.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Alias>,
+}
+
+/// A repository with stored arguments, e.g. `grd download vscodium` downloads
+/// the asset matching the pattern of the "vscodium" alias.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Alias {
+    pub repo: String,
+    pub website_type: Option<GitWebsite>,
+    /// Asset pattern used if none is passed.
+    pub pattern: Option<String>,
+    pub tag: Option<String>,
+    pub tag_pattern: Option<String>,
+    /// Semantic version requirement, e.g. "~1.4" to stay on a version line.
+    pub version: Option<String>,
+    pub tag_prefix: Option<String>,
+    pub prerelease: Option<bool>,
+}
+
+/// Settings for the repositories of a host, used unless they are passed explicitly.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HostConfig {
+    pub website_type: Option<GitWebsite>,
+    pub ip_type: Option<IpType>,
+    /// Sent with every request, headers passed on the command line replace
+    /// the ones with the same name.
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Sent as bearer token, unless an "Authorization" header is set.
+    pub token: Option<TokenSource>,
+    pub ca_bundle: Option<PathBuf>,
+    pub proxy: Option<String>,
+}
+
+/// Where the token of a host is read from, so it does not have to be stored
+/// in the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum TokenSource {
+    /// An environment variable.
+    Env(String),
+    /// A file containing the token.
+    File(PathBuf),
+    /// A shell command printing the token, e.g. of a password manager.
+    Command(String),
+}
+
+/// The configuration file used by default,
+/// `$XDG_CONFIG_HOME/grd/config.toml` or `~/.config/grd/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))
+        .map(|config_home| config_home.join("grd").join("config.toml"))
+}
+
+/// Finds the value of `--config` in the command line arguments.
+///
+/// The configuration provides defaults for the other arguments, so it has to
+/// be read before they are parsed.
+pub fn config_path_from_args(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+// the host (and port) of a repository string like "https://codeberg.org/owner/repo"
+fn host_of(repository_string: &str) -> &str {
+    let without_scheme = repository_string
+        .strip_prefix("https://")
+        .or_else(|| repository_string.strip_prefix("http://"))
+        .unwrap_or(repository_string);
+    without_scheme.split('/').next().unwrap_or_default()
+}
+
+fn header_name(header: &str) -> &str {
+    header
+        .split_once(':')
+        .map_or(header, |(name, _)| name)
+        .trim()
+}
+
+impl Config {
+    /// Reads the configuration at `path`, `None` if it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        let invalid = |reason: String| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(invalid(e.to_string())),
+        };
+        let mut config: Config = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+        config.path = path.to_path_buf();
+        if let Some(output_dir) = &mut config.output_dir {
+            expand_home(output_dir);
+        }
+        for host in config.hosts.values_mut() {
+            if let Some(ca_bundle) = &mut host.ca_bundle {
+                expand_home(ca_bundle);
+            }
+            if let Some(TokenSource::File(file)) = &mut host.token {
+                expand_home(file);
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// Uses the configured defaults as default values of the command line arguments.
+    pub fn apply_defaults(&self, command: clap::Command) -> clap::Command {
+        let subcommands: Vec<String> = command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect();
+        let command = subcommands.iter().fold(command, |command, name| {
+            command.mut_subcommand(name, |subcommand| self.apply_defaults(subcommand))
+        });
+        command.mut_args(|arg| match arg.get_id().as_str() {
+            "allow_prerelease" => match self.prerelease {
+                Some(prerelease) => arg.default_value(prerelease.to_string()),
+                None => arg,
+            },
+            "output_dir" => match &self.output_dir {
+                Some(output_dir) => arg.default_value(output_dir.as_os_str().to_owned()),
+                None => arg,
+            },
+            _ => arg,
+        })
+    }
+
+    /// Uses the stored arguments of the alias passed as repository in `args`,
+    /// and the website and ip type of the host of its repository, as default
+    /// values of the arguments of its subcommand. The alias is replaced by its
+    /// repository.
+    ///
+    /// The remaining settings of the host are applied by
+    /// [`Config::apply_host_settings`] once the arguments have been parsed.
+    pub fn apply_repository(&self, command: clap::Command, args: &[OsString]) -> clap::Command {
+        if self.aliases.is_empty() && self.hosts.is_empty() {
+            return command;
+        }
+        // the asset pattern may be missing, so errors are ignored to find the repository
+        let Ok(mut matches) = command
+            .clone()
+            .ignore_errors(true)
+            .try_get_matches_from(args)
+        else {
+            return command;
+        };
+        let mut subcommand_path = Vec::new();
+        while let Some((name, subcommand_matches)) = matches.remove_subcommand() {
+            subcommand_path.push(name);
+            matches = subcommand_matches;
+        }
+        let Some(repository_string) = matches
+            .try_get_one::<String>("repository")
+            .ok()
+            .flatten()
+            .cloned()
+        else {
+            return command;
+        };
+        let alias = self.aliases.get(&repository_string);
+        let host = self.hosts.get(host_of(
+            alias.map_or(&repository_string, |alias| &alias.repo),
+        ));
+        let website_type = alias
+            .and_then(|alias| alias.website_type.clone())
+            .or_else(|| host.and_then(|host| host.website_type.clone()));
+        let ip_type = host.and_then(|host| host.ip_type);
+
+        let is_passed = |id: &str| {
+            matches.try_get_raw(id).is_ok_and(|value| value.is_some())
+                && matches
+                    .value_source(id)
+                    .is_some_and(|source| source != ValueSource::DefaultValue)
+        };
+        // a passed tag replaces the stored release selection, a passed version
+        // requirement or tag pattern the stored tag, instead of conflicting with it
+        let tag_passed = is_passed("tag");
+        let selection_passed =
+            tag_passed || is_passed("version_requirement") || is_passed("tag_pattern");
+        mut_subcommand_at(command, &subcommand_path, &|subcommand| {
+            subcommand.mut_args(|arg| {
+                let id = arg.get_id().as_str();
+                match id {
+                    "website_type" => {
+                        return match website_type.as_ref().and_then(ValueEnum::to_possible_value) {
+                            Some(website_type) => {
+                                arg.default_value(website_type.get_name().to_string())
+                            }
+                            None => arg,
+                        };
+                    }
+                    "ip_type" => {
+                        return match ip_type {
+                            Some(ip_type) => arg.default_value(ip_type),
+                            None => arg,
+                        };
+                    }
+                    _ => {}
+                }
+                let Some(alias) = alias else {
+                    return arg;
+                };
+                let value = match id {
+                    "repository" => {
+                        let (name, repo) = (repository_string.clone(), alias.repo.clone());
+                        return arg.value_parser(ValueParser::new(
+                            move |value: &str| -> Result<String, Infallible> {
+                                Ok(if value == name { &repo } else { value }.to_string())
+                            },
+                        ));
+                    }
+                    // download and install take the pattern as positional argument,
+                    // "query assets" as option
+                    "asset_pattern" | "pattern" => alias.pattern.clone(),
+                    "tag" if selection_passed => None,
+                    "version_requirement" | "tag_pattern" if tag_passed => None,
+                    "tag" => alias.tag.clone(),
+                    "tag_pattern" => alias.tag_pattern.clone(),
+                    "version_requirement" => alias.version.clone(),
+                    "tag_prefix" => alias.tag_prefix.clone(),
+                    "allow_prerelease" => alias.prerelease.map(|prerelease| prerelease.to_string()),
+                    _ => None,
+                };
+                match value {
+                    Some(value) => arg.required(false).default_value(value),
+                    None => arg,
+                }
+            })
+        })
+    }
+
+    /// Parses a repository like [`resolve_repository`], settings which have not
+    /// been passed are taken from the section of its host.
+    ///
+    /// If `repository_string` is the name of an alias, its repository is used.
+    pub fn resolve_repository(
+        &self,
+        repository_string: String,
+        website_type: Option<GitWebsite>,
+        ip_type: Option<IpType>,
+        headers: Vec<String>,
+    ) -> Result<Repository, Error> {
+        let (repository_string, website_type) = match self.aliases.get(&repository_string) {
+            Some(alias) => (
+                alias.repo.clone(),
+                website_type.or_else(|| alias.website_type.clone()),
+            ),
+            None => (repository_string, website_type),
+        };
+        // the website type is needed to parse the repository, so its host is
+        // taken from the passed string
+        let host = self.hosts.get(host_of(&repository_string));
+        let website_type = website_type.or_else(|| host.and_then(|host| host.website_type.clone()));
+        let ip_type = ip_type
+            .or_else(|| host.and_then(|host| host.ip_type))
+            .unwrap_or(IpType::Any);
+        let repository = resolve_repository(repository_string, website_type, ip_type, headers)?;
+        self.apply_host_settings(repository)
+    }
+
+    /// Adds the headers, token, CA bundle and proxy of the section of the host
+    /// of `repository`.
+    pub fn apply_host_settings(&self, mut repository: Repository) -> Result<Repository, Error> {
+        let Some(host) = self.hosts.get(&repository.origin) else {
+            return Ok(repository);
+        };
+
+        let mut headers = host.headers.clone();
+        if let Some(token) = &host.token {
+            let has_authorization = headers
+                .iter()
+                .chain(&repository.headers)
+                .any(|header| header_name(header).eq_ignore_ascii_case("authorization"));
+            if !has_authorization {
+                let token = token.read().map_err(|reason| Error::InvalidConfig {
+                    path: self.path.clone(),
+                    reason: format!(
+                        "the token of \"{}\" could not be read: {reason}",
+                        repository.origin
+                    ),
+                })?;
+                headers.push(format!("Authorization: Bearer {token}"));
+            }
+        }
+        // the passed headers come last, so they replace the configured ones
+        headers.append(&mut repository.headers);
+        repository.headers = headers;
+        repository.ca_bundle = host.ca_bundle.clone();
+        repository.proxy = host.proxy.clone();
+        Ok(repository)
+    }
+}
+
+fn expand_home(path: &mut PathBuf) {
+    if let (Ok(relative), Some(home)) = (path.strip_prefix("~"), std::env::home_dir()) {
+        *path = home.join(relative);
+    }
+}
+
+impl TokenSource {
+    /// Reads the token, surrounding whitespace is removed.
+    pub fn read(&self) -> Result<String, String> {
+        let token = match self {
+            TokenSource::Env(name) => std::env::var(name)
+                .map_err(|e| format!("could not read the token from ${name}: {e}"))?,
+            TokenSource::File(path) => fs::read_to_string(path).map_err(|e| {
+                format!("could not read the token from \"{}\": {e}", path.display())
+            })?,
+            TokenSource::Command(command) => {
+                let shell = if cfg!(windows) {
+                    ("cmd", "/C")
+                } else {
+                    ("sh", "-c")
+                };
+                let output = Command::new(shell.0)
+                    .args([shell.1, command])
+                    .output()
+                    .map_err(|e| format!("could not run \"{command}\": {e}"))?;
+                if !output.status.success() {
+                    return Err(format!("\"{command}\" failed with {}", output.status));
+                }
+                String::from_utf8(output.stdout)
+                    .map_err(|_| format!("\"{command}\" printed an invalid token"))?
+            }
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err("the token is empty".to_string());
+        }
+        Ok(token.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseRepositoryError;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            prerelease = true
+
+            [hosts."git.example.com:3000"]
+            website-type = "gitea"
+            ip-type = "ipv6"
+            headers = ["X-Client: grd", "Accept-Language: en"]
+            token = { command = "echo ' secret '" }
+            proxy = "http://proxy:3128"
+
+            [hosts."github.com"]
+            headers = ["Authorization: token configured"]
+            token = { env = "GRD_TEST_UNSET_TOKEN" }
+
+            [aliases]
+            vscodium = { repo = "github.com/VSCodium/vscodium", pattern = "\\.deb$" }
+            tool = { repo = "git.example.com:3000/owner/tool", website-type = "gitlab", tag = "v1" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_config_path_from_args() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            config_path_from_args(args(&["grd", "--config", "a.toml", "download"])),
+            Some(PathBuf::from("a.toml"))
+        );
+        assert_eq!(
+            config_path_from_args(args(&["grd", "query", "releases", "--config=b.toml"])),
+            Some(PathBuf::from("b.toml"))
+        );
+        assert_eq!(
+            config_path_from_args(args(&["grd", "download", "--", "--config"])),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_host_settings() {
+        let repository = config()
+            .resolve_repository(
+                "https://git.example.com:3000/owner/repo".to_string(),
+                None,
+                None,
+                vec!["Accept-Language: de".to_string()],
+            )
+            .unwrap();
+        assert_eq!(repository.website, GitWebsite::Gitea);
+        assert_eq!(repository.ip_type, IpType::IPV6);
+        assert_eq!(
+            repository.headers,
+            [
+                "X-Client: grd",
+                "Accept-Language: en",
+                "Authorization: Bearer secret",
+                "Accept-Language: de"
+            ]
+        );
+        assert_eq!(repository.proxy.as_deref(), Some("http://proxy:3128"));
+
+        // passed settings are not replaced
+        let repository = config()
+            .resolve_repository(
+                "git.example.com:3000/owner/repo".to_string(),
+                Some(GitWebsite::GitLab),
+                Some(IpType::IPV4),
+                vec![],
+            )
+            .unwrap();
+        assert_eq!(repository.website, GitWebsite::GitLab);
+        assert_eq!(repository.ip_type, IpType::IPV4);
+    }
+
+    #[test]
+    fn test_token_is_not_read_with_authorization_header() {
+        // the token variable is not set, but the configured header is used instead
+        let repository = config()
+            .resolve_repository("github.com/owner/repo".to_string(), None, None, vec![])
+            .unwrap();
+        assert_eq!(repository.headers, ["Authorization: token configured"]);
+        assert!(TokenSource::Env("GRD_TEST_UNSET_TOKEN".to_string())
+            .read()
+            .is_err());
+    }
+
+    #[test]
+    fn test_unknown_host() {
+        assert!(matches!(
+            config().resolve_repository("codeberg.org/owner/repo".to_string(), None, None, vec![]),
+            Err(Error::InvalidRepository(
+                ParseRepositoryError::UnknownWebsiteType(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let command = clap::Command::new("grd").subcommand(
+            clap::Command::new("download")
+                .arg(
+                    clap::Arg::new("allow_prerelease")
+                        .long("prerelease")
+                        .value_parser(clap::value_parser!(bool))
+                        .default_value("false"),
+                )
+                .arg(clap::Arg::new("output_dir").long("output-dir")),
+        );
+        let matches = config()
+            .apply_defaults(command)
+            .try_get_matches_from(["grd", "download"])
+            .unwrap();
+        let (_, download) = matches.subcommand().unwrap();
+        assert_eq!(download.get_one::<bool>("allow_prerelease"), Some(&true));
+        assert_eq!(download.get_one::<String>("output_dir"), None);
+    }
+
+    #[test]
+    fn test_alias_repository() {
+        let repository = config()
+            .resolve_repository("vscodium".to_string(), None, None, vec![])
+            .unwrap();
+        assert_eq!(repository.website, GitWebsite::GitHub);
+        assert_eq!(repository.owner, "VSCodium");
+        assert_eq!(repository.name, "vscodium");
+
+        // the host settings of the repository of the alias apply, except the
+        // website type stored in the alias
+        let repository = config()
+            .resolve_repository("tool".to_string(), None, None, vec![])
+            .unwrap();
+        assert_eq!(repository.website, GitWebsite::GitLab);
+        assert_eq!(repository.ip_type, IpType::IPV6);
+    }
+
+    #[test]
+    fn test_token_error_is_invalid_config() {
+        let mut config = config();
+        config.path = PathBuf::from("config.toml");
+        config.hosts.get_mut("git.example.com:3000").unwrap().token =
+            Some(TokenSource::Env("GRD_TEST_UNSET_TOKEN".to_string()));
+        assert!(matches!(
+            config.resolve_repository(
+                "git.example.com:3000/owner/repo".to_string(),
+                None,
+                None,
+                vec![]
+            ),
+            Err(Error::InvalidConfig { path, .. }) if path == Path::new("config.toml")
+        ));
+    }
+
+    #[test]
+    fn test_apply_repository() {
+        let parse = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            let command = crate::arguments::Arguments::command();
+            let matches = config()
+                .apply_repository(command, &args)
+                .try_get_matches_from(args)
+                .unwrap();
+            crate::arguments::Arguments::from_arg_matches(&matches).unwrap()
+        };
+        let repository = |args: crate::arguments::Arguments| match args.command_mode {
+            crate::arguments::CommandMode::Download(download_args) => {
+                download_args.asset.repository
+            }
+            _ => unreachable!(),
+        };
+
+        // the alias is replaced by its repository, which is parsed with the
+        // website type of the alias and the ip type of its host
+        let tool = repository(parse(&["grd", "download", "tool", "\\.rpm$"]));
+        assert_eq!(tool.website, GitWebsite::GitLab);
+        assert_eq!(tool.ip_type, IpType::IPV6);
+        assert_eq!(tool.canonical_string(), "git.example.com:3000/owner/tool");
+        // the other host settings are only added by apply_host_settings
+        assert!(tool.headers.is_empty());
+
+        let repository = repository(parse(&[
+            "grd",
+            "download",
+            "--ip-type",
+            "ipv4",
+            "git.example.com:3000/owner/repo",
+            "\\.rpm$",
+        ]));
+        assert_eq!(repository.website, GitWebsite::Gitea);
+        assert_eq!(repository.ip_type, IpType::IPV4);
+    }
+
+    #[test]
+    fn test_apply_alias() {
+        let get_matches = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            let command = crate::arguments::Arguments::command();
+            config()
+                .apply_repository(command, &args)
+                .try_get_matches_from(args)
+        };
+        let asset_pattern_and_tag = |matches: clap::ArgMatches| {
+            let (_, download) = matches.subcommand().unwrap();
+            (
+                download.get_one::<String>("asset_pattern").cloned(),
+                download.get_one::<String>("tag").cloned(),
+            )
+        };
+
+        let matches = get_matches(&["grd", "download", "vscodium"]).unwrap();
+        assert_eq!(
+            asset_pattern_and_tag(matches),
+            (Some("\\.deb$".to_string()), None)
+        );
+        let matches = get_matches(&["grd", "download", "-t", "v2", "tool", "\\.rpm$"]).unwrap();
+        assert_eq!(
+            asset_pattern_and_tag(matches),
+            (Some("\\.rpm$".to_string()), Some("v2".to_string()))
+        );
+        let matches =
+            get_matches(&["grd", "download", "--version", "^2", "tool", "\\.rpm$"]).unwrap();
+        assert_eq!(
+            asset_pattern_and_tag(matches),
+            (Some("\\.rpm$".to_string()), None)
+        );
+        // without a stored pattern it still has to be passed
+        assert!(get_matches(&["grd", "download", "tool"]).is_err());
+        assert!(get_matches(&["grd", "download", "github.com/owner/repo"]).is_err());
+
+        let matches = get_matches(&["grd", "query", "assets", "vscodium"]).unwrap();
+        let (_, query) = matches.subcommand().unwrap();
+        let (_, assets) = query.subcommand().unwrap();
+        assert_eq!(
+            assets.get_one::<String>("pattern").map(String::as_str),
+            Some("\\.deb$")
+        );
+    }
+}
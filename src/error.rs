@@ -0,0 +1,229 @@
+use std::{fmt::Display, path::PathBuf};
+
+use crate::arguments::ParseRepositoryError;
+
+/// Errors that can occur while resolving or downloading release assets.
+///
+/// Every variant maps to a stable process exit code, see [`Error::exit_code`].
+#[derive(Debug)]
+pub enum Error {
+    /// The repository string could not be parsed.
+    InvalidRepository(ParseRepositoryError),
+    /// A http header passed by the user is not in the form "name: value".
+    InvalidHeader(String),
+    /// The asset pattern is not a valid regex.
+    InvalidPattern(regex::Error),
+    /// Any other invalid argument, e.g. a malformed public key.
+    InvalidArgument(String),
+    /// The request could not be sent or no response was received (DNS, connection, TLS, ...).
+    Network(Box<ureq::Transport>),
+    /// The server responded with an unexpected status code.
+    HttpStatus { url: String, status: u16 },
+    /// The server rejected the credentials (or their absence).
+    Unauthorized { url: String, status: u16 },
+    /// The API rate limit has been exceeded.
+    RateLimited { url: String, reset: Option<String> },
+    /// The response body could not be read or deserialized.
+    InvalidResponse(String),
+    /// No release matched the requested tag (or no latest release exists).
+    ReleaseNotFound { tag: Option<String> },
+    /// No release has a tag matching the version requirement.
+    NoMatchingVersion { requirement: String },
+    /// No release has a tag matching the tag pattern.
+    NoMatchingTag { pattern: String },
+    /// The release was found, but none of its assets matched the pattern.
+    NoMatchingAsset {
+        pattern: String,
+        tag: Option<String>,
+        repository: String,
+    },
+    /// Reading the download stream or writing the file failed.
+    Io(std::io::Error),
+    /// The downloaded asset did not pass verification.
+    Verification { asset: String, reason: String },
+    /// The archive is corrupt, unsupported or contains entries escaping the destination.
+    Extraction { archive: String, reason: String },
+    /// The executable could not be located in the asset or not be installed.
+    Install(String),
+    /// A receipt of an installed tool could not be parsed.
+    InvalidReceipt { path: PathBuf, reason: String },
+    /// The manifest could not be read or describes invalid tools.
+    InvalidManifest { path: PathBuf, reason: String },
+    /// The configuration file could not be read or parsed.
+    InvalidConfig { path: PathBuf, reason: String },
+}
+
+impl Error {
+    /// Exit code used by `grd` when terminating because of this error.
+    ///
+    /// These codes are part of the command line interface and must not change:
+    ///
+    /// | code | meaning                                  |
+    /// |------|------------------------------------------|
+    /// | 1    | unspecified error                        |
+    /// | 2    | invalid command line usage               |
+    /// | 3    | invalid repository                       |
+    /// | 4    | invalid argument (header, regex, …)      |
+    /// | 10   | network error                            |
+    /// | 11   | unexpected http status                   |
+    /// | 12   | authentication failed                    |
+    /// | 13   | rate limited                             |
+    /// | 14   | invalid response                         |
+    /// | 20   | release not found                        |
+    /// | 21   | no matching asset                        |
+    /// | 30   | io error                                 |
+    /// | 31   | extraction failed                        |
+    /// | 40   | verification failed                      |
+    /// | 50   | installation failed (or invalid receipt) |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidRepository(_) => 3,
+            Error::InvalidHeader(_)
+            | Error::InvalidPattern(_)
+            | Error::InvalidArgument(_)
+            | Error::InvalidManifest { .. }
+            | Error::InvalidConfig { .. } => 4,
+            Error::Network(_) => 10,
+            Error::HttpStatus { .. } => 11,
+            Error::Unauthorized { .. } => 12,
+            Error::RateLimited { .. } => 13,
+            Error::InvalidResponse(_) => 14,
+            Error::ReleaseNotFound { .. }
+            | Error::NoMatchingVersion { .. }
+            | Error::NoMatchingTag { .. } => 20,
+            Error::NoMatchingAsset { .. } => 21,
+            Error::Io(_) => 30,
+            Error::Extraction { .. } => 31,
+            Error::Verification { .. } => 40,
+            Error::Install(_) | Error::InvalidReceipt { .. } => 50,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidRepository(e) => write!(f, "{e}"),
+            Error::InvalidHeader(header) => write!(
+                f,
+                "Http header \"{header}\" has invalid format, must be: \"header-name: header-value\""
+            ),
+            Error::InvalidPattern(e) => write!(f, "Could not compile RegEx:\n{e}"),
+            Error::InvalidArgument(message) => write!(f, "Invalid argument:\n{message}"),
+            Error::Network(e) => write!(f, "HTTP request failed:\n{e}"),
+            Error::HttpStatus { url, status } => {
+                write!(f, "HTTP request to \"{url}\" failed with status {status}")
+            }
+            Error::Unauthorized { url, status } => write!(
+                f,
+                "HTTP request to \"{url}\" was not authorized (status {status}), check your credentials"
+            ),
+            Error::RateLimited { url, reset } => {
+                write!(f, "HTTP request to \"{url}\" was rate limited")?;
+                if let Some(reset) = reset {
+                    write!(f, ", limit resets at {reset} (unix time)")?;
+                }
+                Ok(())
+            }
+            Error::InvalidResponse(message) => write!(f, "Invalid response:\n{message}"),
+            Error::ReleaseNotFound { tag: Some(tag) } => {
+                write!(f, "Could not find release with tag \"{tag}\"")
+            }
+            Error::ReleaseNotFound { tag: None } => write!(f, "Could not find latest tag"),
+            Error::NoMatchingVersion { requirement } => {
+                write!(f, "Could not find release matching version \"{requirement}\"")
+            }
+            Error::NoMatchingTag { pattern } => {
+                write!(f, "Could not find release with tag matching \"{pattern}\"")
+            }
+            Error::NoMatchingAsset {
+                pattern,
+                tag,
+                repository,
+            } => {
+                let tag_string = match tag {
+                    Some(tag) => format!("tag \"{tag}\""),
+                    None => "latest tag".to_string(),
+                };
+                write!(
+                    f,
+                    r#"Could not find Pattern "{pattern}" in {tag_string} in releases of repository "{repository}""#
+                )
+            }
+            Error::Io(e) => write!(f, "IO error:\n{e}"),
+            Error::Verification { asset, reason } => {
+                write!(f, "Verification of \"{asset}\" failed:\n{reason}")
+            }
+            Error::Extraction { archive, reason } => {
+                write!(f, "Extraction of \"{archive}\" failed:\n{reason}")
+            }
+            Error::Install(message) => write!(f, "Installation failed:\n{message}"),
+            Error::InvalidReceipt { path, reason } => {
+                write!(f, "Invalid receipt \"{}\":\n{reason}", path.display())
+            }
+            Error::InvalidManifest { path, reason } => {
+                write!(f, "Invalid manifest \"{}\":\n{reason}", path.display())
+            }
+            Error::InvalidConfig { path, reason } => {
+                write!(f, "Invalid configuration \"{}\":\n{reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidRepository(e) => Some(e),
+            Error::InvalidPattern(e) => Some(e),
+            Error::Network(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn is_rate_limited(status: u16, response: &ureq::Response) -> bool {
+    // GitHub answers with 403 (sometimes 429) and signals an exhausted
+    // rate limit via its x-ratelimit headers, other forges use 429
+    status == 429 || (status == 403 && response.header("x-ratelimit-remaining") == Some("0"))
+}
+
+impl From<ureq::Error> for Error {
+    fn from(value: ureq::Error) -> Self {
+        match value {
+            ureq::Error::Status(status, response) => {
+                let url = response.get_url().to_string();
+                if is_rate_limited(status, &response) {
+                    let reset = response
+                        .header("x-ratelimit-reset")
+                        .map(ToString::to_string);
+                    Error::RateLimited { url, reset }
+                } else if status == 401 || status == 403 {
+                    Error::Unauthorized { url, status }
+                } else {
+                    Error::HttpStatus { url, status }
+                }
+            }
+            ureq::Error::Transport(transport) => Error::Network(Box::new(transport)),
+        }
+    }
+}
+
+impl From<ParseRepositoryError> for Error {
+    fn from(value: ParseRepositoryError) -> Self {
+        Self::InvalidRepository(value)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(value: regex::Error) -> Self {
+        Self::InvalidPattern(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
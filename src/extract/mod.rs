@@ -0,0 +1,1094 @@
+//! In-process extraction of downloaded archives.
+//!
+//! Archives are treated as hostile: entries must stay inside the destination
+//! directory, neither by `..` components or absolute paths, nor by writing
+//! through symlinks created by earlier entries.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::Error;
+
+mod package;
+
+/// Compression formats of single files and tar archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    /// Guesses the compression from the extension of the file name and
+    /// returns it along with the file name without the extension.
+    pub fn from_file_name(file_name: &str) -> Option<(Self, &str)> {
+        let lowercase = file_name.to_lowercase();
+        [
+            (".gz", Self::Gzip),
+            (".xz", Self::Xz),
+            (".bz2", Self::Bzip2),
+            (".zst", Self::Zstd),
+        ]
+        .into_iter()
+        .find(|(extension, _)| lowercase.ends_with(extension))
+        .map(|(extension, compression)| {
+            (compression, &file_name[..file_name.len() - extension.len()])
+        })
+    }
+
+    /// Detects the compression from the first bytes of the content.
+    pub fn from_magic(content: &[u8]) -> Option<Self> {
+        if content.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if content.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if content.starts_with(b"BZh") {
+            Some(Self::Bzip2)
+        } else if content.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Wraps `reader` in the matching decompressor.
+    pub fn decoder<'r>(self, reader: impl Read + 'r) -> io::Result<Box<dyn Read + 'r>> {
+        Ok(match self {
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::Xz => Box::new(lzma_rust2::XzReader::new(reader, true)),
+            Self::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            Self::Zstd => Box::new(
+                ruzstd::decoding::StreamingDecoder::new(reader)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            ),
+        })
+    }
+}
+
+/// Archive formats that can be extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A tar archive, optionally compressed.
+    Tar(Option<Compression>),
+    Zip,
+    /// A Debian package, the entries of its data archive are extracted.
+    Deb,
+    /// An RPM package, the entries of its cpio payload are extracted.
+    Rpm,
+}
+
+impl ArchiveFormat {
+    /// Guesses the format from the extension of the file name.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let file_name = file_name.to_lowercase();
+        // short forms like "tool.tgz"
+        for (extension, compression) in [
+            (".tgz", Compression::Gzip),
+            (".txz", Compression::Xz),
+            (".tbz2", Compression::Bzip2),
+            (".tzst", Compression::Zstd),
+        ] {
+            if file_name.ends_with(extension) {
+                return Some(Self::Tar(Some(compression)));
+            }
+        }
+        let (compression, file_name) = match Compression::from_file_name(&file_name) {
+            Some((compression, file_name)) => (Some(compression), file_name),
+            None => (None, file_name.as_str()),
+        };
+        if file_name.ends_with(".tar") {
+            Some(Self::Tar(compression))
+        } else if compression.is_some() {
+            None
+        } else if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if file_name.ends_with(".deb") {
+            Some(Self::Deb)
+        } else if file_name.ends_with(".rpm") {
+            Some(Self::Rpm)
+        } else {
+            None
+        }
+    }
+}
+
+/// Controls which entries of an archive are extracted and where they end up.
+#[derive(Debug, Default, Clone)]
+pub struct ExtractOptions {
+    /// Number of leading path components removed from every entry,
+    /// entries with fewer components are skipped.
+    pub strip_components: usize,
+    /// Only entries whose (stripped) path matches one of the globs are extracted.
+    pub include: Option<GlobSet>,
+}
+
+impl ExtractOptions {
+    /// Compiles the include globs, an empty slice extracts everything.
+    pub fn new(strip_components: usize, include_patterns: &[String]) -> Result<Self, Error> {
+        let include = if include_patterns.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in include_patterns {
+                let glob = Glob::new(pattern).map_err(|e| {
+                    Error::InvalidArgument(format!("invalid include glob \"{pattern}\": {e}"))
+                })?;
+                builder.add(glob);
+            }
+            Some(
+                builder
+                    .build()
+                    .map_err(|e| Error::InvalidArgument(format!("invalid include globs: {e}")))?,
+            )
+        };
+        Ok(Self {
+            strip_components,
+            include,
+        })
+    }
+}
+
+/// The entries written by [`extract_stream`], so they can be removed again.
+#[derive(Debug, Default)]
+pub struct ExtractedEntries {
+    /// The extracted files and symlinks.
+    pub files: Vec<PathBuf>,
+    /// The directories which did not exist before, parents before their children.
+    pub created_directories: Vec<PathBuf>,
+}
+
+impl ExtractedEntries {
+    /// Removes the extracted files and then, deepest first, the created
+    /// directories which are empty.
+    pub fn remove(&self) -> io::Result<()> {
+        for file in &self.files {
+            fs::remove_file(file)?;
+        }
+        for directory in self.created_directories.iter().rev() {
+            // a directory something else has been written to is kept
+            let _ = fs::remove_dir(directory);
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the archive at `archive_path` into `destination`, which is created if needed.
+///
+/// Returns the paths of all extracted files and symlinks.
+pub fn extract_archive(
+    archive_path: &Path,
+    format: ArchiveFormat,
+    destination: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    let archive_name = archive_path.display().to_string();
+    let file = File::open(archive_path)?;
+    if format == ArchiveFormat::Zip {
+        let mut extractor = Extractor::new(&archive_name, destination, options)?;
+        extractor.extract_zip(file)?;
+        return Ok(extractor.extracted);
+    }
+    extract_stream(file, format, &archive_name, destination, options).map(|entries| entries.files)
+}
+
+/// Extracts an archive while it is being read, e.g. directly from a download.
+///
+/// Every format except zip can be extracted this way, since the index of zip
+/// archives is at their end. `archive_name` is used in error messages.
+///
+/// Nothing of the archive has been verified yet when the stream breaks off,
+/// so the entries extracted up to an error are removed again.
+pub fn extract_stream(
+    reader: impl Read,
+    format: ArchiveFormat,
+    archive_name: &str,
+    destination: &Path,
+    options: &ExtractOptions,
+) -> Result<ExtractedEntries, Error> {
+    let mut extractor = Extractor::new(archive_name, destination, options)?;
+    let result = match format {
+        ArchiveFormat::Tar(None) => extractor.extract_tar(reader),
+        ArchiveFormat::Tar(Some(compression)) => match compression.decoder(reader) {
+            Ok(reader) => extractor.extract_tar(reader),
+            Err(e) => Err(extractor.error(e.to_string())),
+        },
+        ArchiveFormat::Deb => extractor.extract_deb(reader),
+        ArchiveFormat::Rpm => extractor.extract_rpm(reader),
+        ArchiveFormat::Zip => {
+            Err(extractor.error("zip archives can not be extracted from a stream"))
+        }
+    };
+    let entries = ExtractedEntries {
+        files: extractor.extracted,
+        created_directories: extractor.created_directories,
+    };
+    if let Err(e) = result {
+        // the original error is more useful than one of the cleanup
+        let _ = entries.remove();
+        return Err(e);
+    }
+    Ok(entries)
+}
+
+/// Decompresses the single file at `path` into `out_path`.
+///
+/// If `compression` is `None`, it is detected from the first bytes of the file.
+/// Returns the size of the decompressed file.
+pub fn decompress_file(
+    path: &Path,
+    out_path: &Path,
+    compression: Option<Compression>,
+) -> Result<u64, Error> {
+    let error = |reason: String| Error::Extraction {
+        archive: path.display().to_string(),
+        reason,
+    };
+    let mut file = File::open(path)?;
+    let compression = match compression {
+        Some(compression) => compression,
+        None => {
+            let mut magic = [0; 6];
+            let read = file.read(&mut magic)?;
+            file.seek(io::SeekFrom::Start(0))?;
+            Compression::from_magic(&magic[..read])
+                .ok_or_else(|| error("unknown compression format".to_string()))?
+        }
+    };
+    let mut decoder = compression
+        .decoder(file)
+        .map_err(|e| error(e.to_string()))?;
+    let mut out_file = File::create(out_path)?;
+    io::copy(&mut decoder, &mut out_file).map_err(|e| error(e.to_string()))
+}
+
+/// Decompresses the single file at `path` next to it and removes the compressed file.
+///
+/// The extension of the compression is removed from the name, if the compression
+/// is not recognizable from the name, it is detected from the content and the name
+/// is kept. Returns the path of the decompressed file.
+pub fn decompress_file_in_place(path: &Path) -> Result<PathBuf, Error> {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (compression, out_path) = match Compression::from_file_name(&file_name) {
+        Some((compression, name)) if !name.is_empty() => {
+            (Some(compression), path.with_file_name(name))
+        }
+        _ => (None, path.to_path_buf()),
+    };
+    // the name might not change, so the file can not be written directly
+    let mut part_path = out_path.clone().into_os_string();
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+    if let Err(e) = decompress_file(path, &part_path, compression) {
+        // the compressed file is kept, only the partial output is removed
+        let _ = fs::remove_file(&part_path);
+        return Err(e);
+    }
+    fs::remove_file(path)?;
+    fs::rename(&part_path, &out_path)?;
+    Ok(out_path)
+}
+
+/// Sets the executable bits of the file (a no-op on systems without them).
+pub fn set_executable(path: &Path) -> io::Result<()> {
+    set_permissions(path, Some(0o755))
+}
+
+// the kind of an entry, independent of the archive format
+enum EntryKind {
+    Directory,
+    File,
+    Symlink(PathBuf),
+    Hardlink(PathBuf),
+}
+
+struct Extractor<'o> {
+    archive: String,
+    destination: PathBuf,
+    options: &'o ExtractOptions,
+    extracted: Vec<PathBuf>,
+    created_directories: Vec<PathBuf>,
+    // excluded files of a tar archive are kept here while it is extracted,
+    // since later hard links may point to them
+    staging_dir: Option<PathBuf>,
+    // archive paths of the staged files and their place in the staging directory
+    staged: HashMap<PathBuf, PathBuf>,
+}
+
+impl<'o> Extractor<'o> {
+    fn new(archive: &str, destination: &Path, options: &'o ExtractOptions) -> Result<Self, Error> {
+        let mut created_directories: Vec<_> = destination
+            .ancestors()
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        created_directories.reverse();
+        fs::create_dir_all(destination)?;
+        Ok(Self {
+            archive: archive.to_string(),
+            destination: destination.to_path_buf(),
+            options,
+            extracted: vec![],
+            created_directories,
+            staging_dir: None,
+            staged: HashMap::new(),
+        })
+    }
+
+    fn error(&self, reason: impl Into<String>) -> Error {
+        Error::Extraction {
+            archive: self.archive.clone(),
+            reason: reason.into(),
+        }
+    }
+
+    fn extract_tar(&mut self, reader: impl Read) -> Result<(), Error> {
+        let staging_dir = self
+            .destination
+            .join(format!(".grd-excluded-{}", std::process::id()));
+        self.staging_dir = Some(staging_dir.clone());
+        let result = self.extract_tar_entries(reader);
+        self.staging_dir = None;
+        self.staged.clear();
+        let removed = match staging_dir.exists() {
+            true => fs::remove_dir_all(&staging_dir),
+            false => Ok(()),
+        };
+        result?;
+        Ok(removed?)
+    }
+
+    fn extract_tar_entries(&mut self, reader: impl Read) -> Result<(), Error> {
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive.entries().map_err(|e| self.error(e.to_string()))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| self.error(e.to_string()))?;
+            let path = entry
+                .path()
+                .map_err(|e| self.error(e.to_string()))?
+                .into_owned();
+            let link_name = || -> Result<PathBuf, Error> {
+                entry
+                    .link_name()
+                    .map_err(|e| self.error(e.to_string()))?
+                    .map(|link_name| link_name.into_owned())
+                    .ok_or_else(|| self.error(format!("link \"{}\" has no target", path.display())))
+            };
+            let kind = match entry.header().entry_type() {
+                tar::EntryType::Directory => EntryKind::Directory,
+                tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+                tar::EntryType::Symlink => EntryKind::Symlink(link_name()?),
+                tar::EntryType::Link => EntryKind::Hardlink(link_name()?),
+                // devices, fifos and metadata entries are never needed from a release asset
+                _ => continue,
+            };
+            let mode = entry.header().mode().ok();
+            self.extract_entry(&path, kind, mode, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn extract_zip(&mut self, file: File) -> Result<(), Error> {
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| self.error(e.to_string()))?;
+        for index in 0..archive.len() {
+            let mut entry = archive
+                .by_index(index)
+                .map_err(|e| self.error(e.to_string()))?;
+            let path = PathBuf::from(entry.name());
+            let kind = if entry.is_dir() {
+                EntryKind::Directory
+            } else if entry.is_symlink() {
+                // the target of a symlink is stored as its content
+                let mut target = String::new();
+                entry
+                    .read_to_string(&mut target)
+                    .map_err(|e| self.error(e.to_string()))?;
+                EntryKind::Symlink(PathBuf::from(target))
+            } else {
+                EntryKind::File
+            };
+            let mode = entry.unix_mode();
+            self.extract_entry(&path, kind, mode, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn extract_entry(
+        &mut self,
+        entry_path: &Path,
+        kind: EntryKind,
+        mode: Option<u32>,
+        content: &mut impl Read,
+    ) -> Result<(), Error> {
+        let Some(relative_path) = sanitize_entry_path(entry_path, self.options.strip_components)
+            .map_err(|reason| self.error(reason))?
+            .filter(|path| self.is_included(path, &kind))
+        else {
+            return self.stage_excluded(entry_path, &kind, mode, content);
+        };
+
+        let out_path = self.prepare_path(&relative_path)?;
+        match kind {
+            EntryKind::Directory => {
+                if !out_path.is_dir() {
+                    fs::create_dir(&out_path)?;
+                    self.created_directories.push(out_path);
+                }
+                return Ok(());
+            }
+            EntryKind::File => {
+                let mut out_file = File::create(&out_path)?;
+                // recorded before the content is written, so a partially
+                // written file is removed along with the other entries
+                self.extracted.push(out_path.clone());
+                io::copy(content, &mut out_file)?;
+                set_permissions(&out_path, mode)?;
+                return Ok(());
+            }
+            EntryKind::Symlink(target) => {
+                check_symlink_target(&relative_path, &target)
+                    .map_err(|reason| self.error(reason))?;
+                create_symlink(&target, &out_path)?;
+            }
+            EntryKind::Hardlink(target) => {
+                // the target is an earlier entry of the archive, which has
+                // either been extracted or staged
+                let source = match self.extracted_target(&target)? {
+                    Some(source) => source,
+                    None => self.staged_target(&target)?.ok_or_else(|| {
+                        self.error(format!(
+                            "hard link \"{}\" points to \"{}\", which has not been extracted",
+                            entry_path.display(),
+                            target.display()
+                        ))
+                    })?,
+                };
+                fs::hard_link(source, &out_path)?;
+            }
+        }
+        self.extracted.push(out_path);
+        Ok(())
+    }
+
+    fn is_included(&self, relative_path: &Path, kind: &EntryKind) -> bool {
+        match &self.options.include {
+            // directories are created on demand for included entries
+            Some(include) => {
+                !matches!(kind, EntryKind::Directory) && include.is_match(relative_path)
+            }
+            None => true,
+        }
+    }
+
+    // whether a file at the path of the archive would be extracted
+    fn includes_file(&self, entry_path: &Path) -> Result<bool, Error> {
+        Ok(
+            sanitize_entry_path(entry_path, self.options.strip_components)
+                .map_err(|reason| self.error(reason))?
+                .is_some_and(|path| self.is_included(&path, &EntryKind::File)),
+        )
+    }
+
+    // keeps the content of an excluded file for later hard links to it
+    fn stage_excluded(
+        &mut self,
+        entry_path: &Path,
+        kind: &EntryKind,
+        mode: Option<u32>,
+        content: &mut impl Read,
+    ) -> Result<(), Error> {
+        let Some(staging_dir) = self.staging_dir.clone() else {
+            return Ok(());
+        };
+        let Some(archive_path) =
+            sanitize_entry_path(entry_path, 0).map_err(|reason| self.error(reason))?
+        else {
+            return Ok(());
+        };
+        let staged_path = match kind {
+            EntryKind::File => {
+                if !staging_dir.exists() {
+                    fs::create_dir(&staging_dir)?;
+                }
+                let staged_path = staging_dir.join(self.staged.len().to_string());
+                io::copy(content, &mut File::create(&staged_path)?)?;
+                set_permissions(&staged_path, mode)?;
+                staged_path
+            }
+            // a link to a staged file is as good as the file itself
+            EntryKind::Hardlink(target) => match self.staged_target(target)? {
+                Some(staged_path) => staged_path,
+                None => return Ok(()),
+            },
+            EntryKind::Directory | EntryKind::Symlink(_) => return Ok(()),
+        };
+        self.staged.insert(archive_path, staged_path);
+        Ok(())
+    }
+
+    // the extracted file a hard link points to
+    fn extracted_target(&self, target: &Path) -> Result<Option<PathBuf>, Error> {
+        let Some(target) = sanitize_entry_path(target, self.options.strip_components)
+            .map_err(|reason| self.error(reason))?
+        else {
+            return Ok(None);
+        };
+        let out_path = self.destination.join(&target);
+        if !self.extracted.contains(&out_path) {
+            return Ok(None);
+        }
+        self.check_no_symlinks(&target)?;
+        Ok(Some(out_path))
+    }
+
+    // the staged file a hard link points to
+    fn staged_target(&self, target: &Path) -> Result<Option<PathBuf>, Error> {
+        Ok(sanitize_entry_path(target, 0)
+            .map_err(|reason| self.error(reason))?
+            .and_then(|target| self.staged.get(&target).cloned()))
+    }
+
+    // creates the parent directories of the entry and removes an existing
+    // file or symlink at its place, so nothing is ever written through a symlink
+    fn prepare_path(&mut self, relative_path: &Path) -> Result<PathBuf, Error> {
+        let mut path = self.destination.clone();
+        let component_count = relative_path.components().count();
+        for (index, component) in relative_path.components().enumerate() {
+            path.push(component);
+            let is_last = index + 1 == component_count;
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_symlink() => {
+                    if is_last {
+                        fs::remove_file(&path)?;
+                    } else {
+                        return Err(self.error(format!(
+                            "entry \"{}\" would be written through the symlink \"{}\"",
+                            relative_path.display(),
+                            path.display()
+                        )));
+                    }
+                }
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) if is_last => fs::remove_file(&path)?,
+                Ok(_) => {
+                    return Err(self.error(format!(
+                        "entry \"{}\" needs \"{}\" to be a directory",
+                        relative_path.display(),
+                        path.display()
+                    )))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    if !is_last {
+                        fs::create_dir(&path)?;
+                        self.created_directories.push(path.clone());
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(path)
+    }
+
+    fn check_no_symlinks(&self, relative_path: &Path) -> Result<(), Error> {
+        let mut path = self.destination.clone();
+        for component in relative_path.components() {
+            path.push(component);
+            if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_symlink()) {
+                return Err(self.error(format!(
+                    "entry \"{}\" would be accessed through the symlink \"{}\"",
+                    relative_path.display(),
+                    path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Turns the path of an archive entry into a plain relative path and removes
+/// the first `strip_components` components.
+///
+/// Returns `None` if nothing is left after stripping and an error if the path
+/// is absolute or contains `..`.
+fn sanitize_entry_path(path: &Path, strip_components: usize) -> Result<Option<PathBuf>, String> {
+    let mut components = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => components.push(component),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(format!(
+                    "entry \"{}\" points outside of the destination",
+                    path.display()
+                ))
+            }
+        }
+    }
+    if components.len() <= strip_components {
+        return Ok(None);
+    }
+    Ok(Some(components[strip_components..].iter().collect()))
+}
+
+/// Makes sure a symlink at `link_path` (relative to the destination) can not
+/// point outside of the destination.
+///
+/// `..` is only allowed at the start of the target, otherwise a symlink
+/// created by an earlier entry could make the lexical check meaningless
+/// (e.g. "dir/link/.." with "dir/link" pointing to "..").
+fn check_symlink_target(link_path: &Path, target: &Path) -> Result<(), String> {
+    let error = || {
+        format!(
+            "symlink \"{}\" points outside of the destination (\"{}\")",
+            link_path.display(),
+            target.display()
+        )
+    };
+    let mut depth = link_path.components().count() as isize - 1;
+    let mut seen_normal = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => {
+                seen_normal = true;
+                depth += 1;
+            }
+            Component::CurDir => {}
+            Component::ParentDir if !seen_normal => {
+                depth -= 1;
+                if depth < 0 {
+                    return Err(error());
+                }
+            }
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return Err(error()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_permissions(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match mode {
+        // setuid, setgid and sticky bits are dropped
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_permissions(_path: &Path, _mode: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_dir, temp_path};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/extract");
+
+    // the tar builder refuses unsafe paths, so the header is filled manually
+    fn append_raw(
+        builder: &mut tar::Builder<Vec<u8>>,
+        path: &str,
+        entry_type: tar::EntryType,
+        link_name: Option<&str>,
+        content: &[u8],
+    ) {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        if let Some(link_name) = link_name {
+            header.as_old_mut().linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+        }
+        header.set_entry_type(entry_type);
+        header.set_mode(0o755);
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder.append(&header, content).unwrap();
+    }
+
+    fn tar_gz(build: impl FnOnce(&mut tar::Builder<Vec<u8>>)) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        build(&mut builder);
+        let tar = builder.into_inner().unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // returns the destination, which has to be removed by the caller
+    fn extract(
+        name: &str,
+        archive: &[u8],
+        options: &ExtractOptions,
+    ) -> (PathBuf, Result<Vec<PathBuf>, Error>) {
+        let dir = temp_path("extract", name);
+        let archive_path = dir.with_extension("tar.gz");
+        fs::write(&archive_path, archive).unwrap();
+        let result = extract_archive(
+            &archive_path,
+            ArchiveFormat::Tar(Some(Compression::Gzip)),
+            &dir,
+            options,
+        );
+        fs::remove_file(&archive_path).unwrap();
+        (dir, result)
+    }
+
+    #[test]
+    fn test_format_from_file_name() {
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool-1.0-linux.tar.gz"),
+            Some(ArchiveFormat::Tar(Some(Compression::Gzip)))
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool.TGZ"),
+            Some(ArchiveFormat::Tar(Some(Compression::Gzip)))
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool.tar.zst"),
+            Some(ArchiveFormat::Tar(Some(Compression::Zstd)))
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool.zip"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool.tbz2"),
+            Some(ArchiveFormat::Tar(Some(Compression::Bzip2)))
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool.tar"),
+            Some(ArchiveFormat::Tar(None))
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool_1.0_amd64.deb"),
+            Some(ArchiveFormat::Deb)
+        );
+        assert_eq!(
+            ArchiveFormat::from_file_name("tool-1.0-1.x86_64.rpm"),
+            Some(ArchiveFormat::Rpm)
+        );
+        assert_eq!(ArchiveFormat::from_file_name("tool.gz"), None);
+        assert_eq!(ArchiveFormat::from_file_name("tool.exe"), None);
+    }
+
+    #[test]
+    fn test_compression_detection() {
+        assert_eq!(
+            Compression::from_file_name("tool-linux-amd64.GZ"),
+            Some((Compression::Gzip, "tool-linux-amd64"))
+        );
+        assert_eq!(
+            Compression::from_file_name("tool.bz2"),
+            Some((Compression::Bzip2, "tool"))
+        );
+        assert_eq!(Compression::from_file_name("tool"), None);
+        assert_eq!(
+            Compression::from_magic(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_magic(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn test_decompress_file() {
+        let out_path = temp_path("extract", "hello");
+        // detected from the magic bytes
+        decompress_file(&Path::new(FIXTURES).join("hello.bz2"), &out_path, None).unwrap();
+        assert_eq!(fs::read(&out_path).unwrap(), b"hello\n");
+
+        let result = decompress_file(&Path::new(FIXTURES).join("test.zip"), &out_path, None);
+        assert!(matches!(result, Err(Error::Extraction { .. })));
+        fs::remove_file(out_path).unwrap();
+    }
+
+    #[test]
+    fn test_decompress_file_in_place() {
+        let dir = temp_dir("extract", "in-place");
+        let path = dir.join("hello.bz2");
+        fs::copy(Path::new(FIXTURES).join("hello.bz2"), &path).unwrap();
+        let out_path = decompress_file_in_place(&path).unwrap();
+        assert_eq!(out_path, dir.join("hello"));
+        assert_eq!(fs::read(&out_path).unwrap(), b"hello\n");
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize_entry_path() {
+        assert_eq!(
+            sanitize_entry_path(Path::new("./tool-1.0/bin/tool"), 1),
+            Ok(Some(PathBuf::from("bin/tool")))
+        );
+        assert_eq!(sanitize_entry_path(Path::new("tool-1.0/"), 1), Ok(None));
+        assert!(sanitize_entry_path(Path::new("tool/../../etc/passwd"), 0).is_err());
+        assert!(sanitize_entry_path(Path::new("/etc/passwd"), 0).is_err());
+    }
+
+    #[test]
+    fn test_check_symlink_target() {
+        assert!(check_symlink_target(Path::new("bin/tool"), Path::new("../lib/tool")).is_ok());
+        assert!(check_symlink_target(Path::new("bin/tool"), Path::new("tool-1.0")).is_ok());
+        assert!(check_symlink_target(Path::new("tool"), Path::new("../tool")).is_err());
+        assert!(check_symlink_target(Path::new("bin/tool"), Path::new("/usr/bin/tool")).is_err());
+        assert!(check_symlink_target(Path::new("a/b"), Path::new("c/../..")).is_err());
+    }
+
+    #[test]
+    fn test_extract_strip_components_and_include() {
+        let archive = tar_gz(|builder| {
+            append_raw(builder, "tool-1.0/", tar::EntryType::Directory, None, b"");
+            append_raw(
+                builder,
+                "tool-1.0/bin/tool",
+                tar::EntryType::Regular,
+                None,
+                b"bin",
+            );
+            append_raw(
+                builder,
+                "tool-1.0/README",
+                tar::EntryType::Regular,
+                None,
+                b"doc",
+            );
+            append_raw(
+                builder,
+                "tool-1.0/bin/alias",
+                tar::EntryType::Symlink,
+                Some("tool"),
+                b"",
+            );
+        });
+        let options = ExtractOptions::new(1, &["bin/*".to_string()]).unwrap();
+        let (dir, result) = extract("include", &archive, &options);
+        result.unwrap();
+        assert_eq!(fs::read(dir.join("bin/tool")).unwrap(), b"bin");
+        assert_eq!(fs::read(dir.join("bin/alias")).unwrap(), b"bin");
+        assert!(!dir.join("README").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("bin/tool"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_hard_link_to_excluded_entry() {
+        let archive = tar_gz(|builder| {
+            append_raw(
+                builder,
+                "pkg/tool",
+                tar::EntryType::Regular,
+                None,
+                b"tool\n",
+            );
+            append_raw(
+                builder,
+                "pkg/bin/alias",
+                tar::EntryType::Link,
+                Some("pkg/tool"),
+                b"",
+            );
+        });
+        // the target is not included
+        let options = ExtractOptions::new(1, &["bin/alias".to_string()]).unwrap();
+        let (dir, result) = extract("hardlink-include", &archive, &options);
+        assert_eq!(result.unwrap(), vec![dir.join("bin/alias")]);
+        assert_eq!(fs::read(dir.join("bin/alias")).unwrap(), b"tool\n");
+        // the staged content is gone
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+
+        // the path of the target is stripped
+        let options = ExtractOptions::new(2, &[]).unwrap();
+        let (dir, result) = extract("hardlink-strip", &archive, &options);
+        assert_eq!(result.unwrap(), vec![dir.join("alias")]);
+        assert_eq!(fs::read(dir.join("alias")).unwrap(), b"tool\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reject_path_traversal() {
+        let archive = tar_gz(|builder| {
+            append_raw(builder, "../evil", tar::EntryType::Regular, None, b"evil");
+        });
+        let (dir, result) = extract("traversal", &archive, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::Extraction { .. })));
+        // the destination was created by the failed extraction
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reject_symlink_escape() {
+        let archive = tar_gz(|builder| {
+            append_raw(builder, "link", tar::EntryType::Symlink, Some("/tmp"), b"");
+        });
+        let (dir, result) = extract("symlink-escape", &archive, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::Extraction { .. })));
+        // the destination was created by the failed extraction
+        assert!(!dir.exists());
+
+        // a symlink inside the destination must not be written through either
+        let archive = tar_gz(|builder| {
+            append_raw(builder, "dir/", tar::EntryType::Directory, None, b"");
+            append_raw(builder, "link", tar::EntryType::Symlink, Some("dir"), b"");
+            append_raw(builder, "link/file", tar::EntryType::Regular, None, b"file");
+        });
+        let (dir, result) = extract("symlink-write", &archive, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::Extraction { .. })));
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_extract_fixtures() {
+        for (file_name, format) in [
+            ("test.tar.xz", ArchiveFormat::Tar(Some(Compression::Xz))),
+            ("test.tar.zst", ArchiveFormat::Tar(Some(Compression::Zstd))),
+            ("test.zip", ArchiveFormat::Zip),
+        ] {
+            let dir = temp_path("extract", file_name);
+            let extracted = extract_archive(
+                &Path::new(FIXTURES).join(file_name),
+                format,
+                &dir,
+                &ExtractOptions::new(1, &[]).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(extracted, vec![dir.join("hello.txt")], "{file_name}");
+            assert_eq!(fs::read(dir.join("hello.txt")).unwrap(), b"hello\n");
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_extract_stream_hashes_whole_archive() {
+        let archive = fs::read(Path::new(FIXTURES).join("test.tar.zst")).unwrap();
+        let algorithms = [crate::verify::DigestAlgorithm::Sha256];
+        let mut reader = crate::verify::HashingReader::new(&archive[..], &algorithms);
+        let dir = temp_path("extract", "stream");
+        let extracted = extract_stream(
+            &mut reader,
+            ArchiveFormat::Tar(Some(Compression::Zstd)),
+            "test.tar.zst",
+            &dir,
+            &ExtractOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(extracted.files, vec![dir.join("test/hello.txt")]);
+        // like the download does, read what the decompressor left over
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        assert_eq!(reader.bytes_read(), archive.len() as u64);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_extracted_entries() {
+        let archive = tar_gz(|builder| {
+            append_raw(builder, "empty/", tar::EntryType::Directory, None, b"");
+            append_raw(builder, "bin/tool", tar::EntryType::Regular, None, b"bin");
+            append_raw(
+                builder,
+                "share/doc/README",
+                tar::EntryType::Regular,
+                None,
+                b"doc",
+            );
+        });
+        let extract_into = |destination: &Path| {
+            extract_stream(
+                &archive[..],
+                ArchiveFormat::Tar(Some(Compression::Gzip)),
+                "test.tar.gz",
+                destination,
+                &ExtractOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // directories which existed before or contain other files are kept
+        let dir = temp_path("extract", "remove");
+        fs::create_dir_all(dir.join("share")).unwrap();
+        let extracted = extract_into(&dir);
+        fs::write(dir.join("bin/other"), "other").unwrap();
+        extracted.remove().unwrap();
+        assert!(!dir.join("empty").exists());
+        assert!(!dir.join("bin/tool").exists());
+        assert!(dir.join("bin/other").exists());
+        assert!(!dir.join("share/doc").exists());
+        assert!(dir.join("share").is_dir());
+
+        // a destination created by the extraction is removed as well
+        let extracted = extract_into(&dir.join("new/destination"));
+        extracted.remove().unwrap();
+        assert!(!dir.join("new").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_stream_removes_entries_on_error() {
+        let content: Vec<u8> = (0..64 * 1024u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let archive = tar_gz(|builder| {
+            append_raw(
+                builder,
+                "bin/small",
+                tar::EntryType::Regular,
+                None,
+                b"small",
+            );
+            append_raw(
+                builder,
+                "bin/large",
+                tar::EntryType::Regular,
+                None,
+                &content,
+            );
+        });
+        let truncated = &archive[..archive.len() / 2];
+
+        let dir = temp_dir("extract", "truncated");
+        let result = extract_stream(
+            truncated,
+            ArchiveFormat::Tar(Some(Compression::Gzip)),
+            "test.tar.gz",
+            &dir,
+            &ExtractOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Extraction { .. }) | Err(Error::Io(_))
+        ));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
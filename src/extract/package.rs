@@ -0,0 +1,311 @@
+//! Reading the files of .deb and .rpm packages without dpkg or rpm.
+//!
+//! Both formats are read sequentially, so packages can be extracted while
+//! they are being downloaded.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use super::{Compression, EntryKind, Extractor};
+use crate::Error;
+
+const AR_MAGIC: &[u8; 8] = b"!<arch>\n";
+const RPM_LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const RPM_HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+const CPIO_TRAILER: &str = "TRAILER!!!";
+// protects against allocating huge buffers for hostile headers
+const MAX_CPIO_NAME_SIZE: u64 = 64 * 1024;
+
+// file type bits of the mode of cpio entries
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+fn skip(reader: &mut impl Read, length: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(length), &mut io::sink())?;
+    if skipped < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn parse_number(field: &[u8], radix: u32) -> Option<u64> {
+    let field = std::str::from_utf8(field).ok()?.trim();
+    u64::from_str_radix(field, radix).ok()
+}
+
+impl Extractor<'_> {
+    /// Extracts the data archive of a Debian package, which is an ar archive
+    /// containing "debian-binary", "control.tar.*" and "data.tar.*".
+    pub(super) fn extract_deb(&mut self, mut reader: impl Read) -> Result<(), Error> {
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| self.error(e.to_string()))?;
+        if &magic != AR_MAGIC {
+            return Err(self.error("not a Debian package (missing ar header)"));
+        }
+
+        loop {
+            let mut header = [0; 60];
+            if let Err(e) = reader.read_exact(&mut header) {
+                return Err(if e.kind() == io::ErrorKind::UnexpectedEof {
+                    self.error("Debian package does not contain a data archive")
+                } else {
+                    self.error(e.to_string())
+                });
+            }
+            // gnu ar terminates names with a slash
+            let name = String::from_utf8_lossy(&header[..16])
+                .trim_end()
+                .trim_end_matches('/')
+                .to_string();
+            let size = parse_number(&header[48..58], 10)
+                .ok_or_else(|| self.error(format!("invalid size of ar member \"{name}\"")))?;
+
+            if name == "data.tar" {
+                return self.extract_tar((&mut reader).take(size));
+            }
+            if name.starts_with("data.tar.") {
+                let compression = match Compression::from_file_name(&name) {
+                    Some((compression, "data.tar")) => compression,
+                    _ => return Err(self.error(format!("unsupported compression of \"{name}\""))),
+                };
+                let data = compression
+                    .decoder((&mut reader).take(size))
+                    .map_err(|e| self.error(e.to_string()))?;
+                return self.extract_tar(data);
+            }
+            // members are aligned to two bytes
+            skip(&mut reader, size + size % 2).map_err(|e| self.error(e.to_string()))?;
+        }
+    }
+
+    /// Extracts the cpio payload of an RPM package.
+    ///
+    /// The lead and the headers in front of the payload are skipped, the
+    /// compression of the payload is detected from its content.
+    pub(super) fn extract_rpm(&mut self, mut reader: impl Read) -> Result<(), Error> {
+        let mut lead = [0; 96];
+        reader
+            .read_exact(&mut lead)
+            .map_err(|e| self.error(e.to_string()))?;
+        if lead[..4] != RPM_LEAD_MAGIC {
+            return Err(self.error("not an RPM package (missing lead)"));
+        }
+        // the signature header is padded to a multiple of 8 bytes
+        let signature_length = self.skip_rpm_header(&mut reader)?;
+        skip(&mut reader, (8 - signature_length % 8) % 8).map_err(|e| self.error(e.to_string()))?;
+        self.skip_rpm_header(&mut reader)?;
+
+        let mut magic = [0; 6];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| self.error(e.to_string()))?;
+        let payload = io::Cursor::new(magic).chain(reader);
+        match Compression::from_magic(&magic) {
+            Some(compression) => {
+                let payload = compression
+                    .decoder(payload)
+                    .map_err(|e| self.error(e.to_string()))?;
+                self.extract_cpio(payload)
+            }
+            None if magic.starts_with(b"0707") => self.extract_cpio(payload),
+            None => Err(self.error("unsupported compression of the RPM payload")),
+        }
+    }
+
+    // returns the length of the header
+    fn skip_rpm_header(&self, reader: &mut impl Read) -> Result<u64, Error> {
+        let mut header = [0; 16];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| self.error(e.to_string()))?;
+        if header[..3] != RPM_HEADER_MAGIC {
+            return Err(self.error("invalid RPM header"));
+        }
+        let index_count = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+        let data_length = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+        // every index entry has 16 bytes
+        let length = 16 * u64::from(index_count) + u64::from(data_length);
+        skip(reader, length).map_err(|e| self.error(e.to_string()))?;
+        Ok(16 + length)
+    }
+
+    // reads a cpio archive in the "new ascii" format (with or without checksums)
+    fn extract_cpio(&mut self, mut reader: impl Read) -> Result<(), Error> {
+        // hard linked files store their content only with the last entry,
+        // the entries before it are linked to it once it has been extracted
+        let mut pending_links: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+        loop {
+            let mut header = [0; 110];
+            reader
+                .read_exact(&mut header)
+                .map_err(|e| self.error(e.to_string()))?;
+            if &header[..6] != b"070701" && &header[..6] != b"070702" {
+                return Err(self.error("unsupported cpio format of the payload"));
+            }
+            let field = |index: usize| {
+                parse_number(&header[6 + 8 * index..14 + 8 * index], 16)
+                    .ok_or_else(|| self.error("invalid cpio header"))
+            };
+            let inode = field(0)?;
+            let mode = field(1)? as u32;
+            let link_count = field(4)?;
+            let file_size = field(6)?;
+            let name_size = field(11)?;
+            if name_size > MAX_CPIO_NAME_SIZE {
+                return Err(self.error("cpio entry name is too long"));
+            }
+
+            let mut name = vec![0; name_size as usize];
+            reader
+                .read_exact(&mut name)
+                .map_err(|e| self.error(e.to_string()))?;
+            // header and name are padded to a multiple of 4 bytes
+            skip(&mut reader, (4 - (110 + name_size) % 4) % 4)
+                .map_err(|e| self.error(e.to_string()))?;
+            let name = String::from_utf8_lossy(&name)
+                .trim_end_matches('\0')
+                .to_string();
+            if name == CPIO_TRAILER {
+                break;
+            }
+            let path = PathBuf::from(name);
+
+            let mut content = (&mut reader).take(file_size);
+            let kind = match mode & S_IFMT {
+                S_IFDIR => Some(EntryKind::Directory),
+                S_IFREG if link_count > 1 && file_size == 0 => {
+                    pending_links.entry(inode).or_default().push(path.clone());
+                    None
+                }
+                S_IFREG => Some(EntryKind::File),
+                S_IFLNK => {
+                    let mut target = String::new();
+                    content
+                        .read_to_string(&mut target)
+                        .map_err(|e| self.error(e.to_string()))?;
+                    Some(EntryKind::Symlink(PathBuf::from(target)))
+                }
+                // devices and fifos are never needed from a release asset
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let is_file = matches!(kind, EntryKind::File);
+                let mut links = match is_file {
+                    true => pending_links.remove(&inode).unwrap_or_default(),
+                    false => vec![],
+                };
+                // without the entry carrying the content, the first included
+                // link gets it and the others are linked to that one
+                let mut source = path;
+                if is_file && !self.includes_file(&source)? {
+                    for index in 0..links.len() {
+                        if self.includes_file(&links[index])? {
+                            source = links.remove(index);
+                            break;
+                        }
+                    }
+                }
+                self.extract_entry(&source, kind, Some(mode), &mut content)?;
+                for link in links {
+                    let kind = EntryKind::Hardlink(source.clone());
+                    self.extract_entry(&link, kind, None, &mut io::empty())?;
+                }
+            }
+            io::copy(&mut content, &mut io::sink()).map_err(|e| self.error(e.to_string()))?;
+            skip(&mut reader, (4 - file_size % 4) % 4).map_err(|e| self.error(e.to_string()))?;
+        }
+
+        // hard linked files without content
+        for path in pending_links.into_values().flatten() {
+            self.extract_entry(&path, EntryKind::File, None, &mut io::empty())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::super::{extract_archive, ArchiveFormat, ExtractOptions};
+    use crate::{test_util::temp_path, Error};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/extract");
+
+    #[test]
+    fn test_extract_deb() {
+        let dir = temp_path("package", "deb");
+        let extracted = extract_archive(
+            &Path::new(FIXTURES).join("test.deb"),
+            ArchiveFormat::Deb,
+            &dir,
+            &ExtractOptions::new(0, &["usr/bin/*".to_string()]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(extracted, vec![dir.join("usr/bin/tool")]);
+        assert_eq!(fs::read(dir.join("usr/bin/tool")).unwrap(), b"tool\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_rpm() {
+        let dir = temp_path("package", "rpm");
+        let extracted = extract_archive(
+            &Path::new(FIXTURES).join("test.rpm"),
+            ArchiveFormat::Rpm,
+            &dir,
+            &ExtractOptions::new(2, &[]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            extracted,
+            vec![
+                dir.join("tool"),
+                dir.join("tool-alias"),
+                dir.join("tool-link")
+            ]
+        );
+        assert_eq!(fs::read(dir.join("tool")).unwrap(), b"tool\n");
+        // hard link and symlink
+        assert_eq!(fs::read(dir.join("tool-alias")).unwrap(), b"tool\n");
+        assert_eq!(fs::read(dir.join("tool-link")).unwrap(), b"tool\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_rpm_hard_link_only() {
+        // the content belongs to the excluded "tool" in the payload
+        let dir = temp_path("package", "rpm-link");
+        let extracted = extract_archive(
+            &Path::new(FIXTURES).join("test.rpm"),
+            ArchiveFormat::Rpm,
+            &dir,
+            &ExtractOptions::new(2, &["tool-alias".to_string()]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(extracted, vec![dir.join("tool-alias")]);
+        assert_eq!(fs::read(dir.join("tool-alias")).unwrap(), b"tool\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reject_invalid_package() {
+        let dir = temp_path("package", "invalid");
+        let result = extract_archive(
+            &Path::new(FIXTURES).join("test.zip"),
+            ArchiveFormat::Deb,
+            &dir,
+            &ExtractOptions::default(),
+        );
+        assert!(matches!(result, Err(Error::Extraction { .. })));
+        assert!(!dir.exists());
+    }
+}
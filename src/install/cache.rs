@@ -0,0 +1,102 @@
+//! Assets downloaded for installations are kept in a cache, so installing a
+//! release again (e.g. after its version has been garbage collected) does not
+//! download it again.
+//!
+//! ```text
+//! <cache dir>/<origin>/<owner>/<repo>/<tag>/<asset>
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::version_dir_name;
+use crate::Repository;
+
+/// The directory downloaded assets are cached in by default,
+/// `$XDG_CACHE_HOME/grd` or `~/.cache/grd`.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|cache_home| !cache_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cache")))
+        .map(|cache_home| cache_home.join("grd"))
+}
+
+/// Path of the cached copy of the asset `asset_name` of the release `tag`.
+pub fn cache_path(
+    cache_dir: &Path,
+    repository: &Repository,
+    tag: &str,
+    asset_name: &str,
+) -> PathBuf {
+    let mut path = cache_dir.to_path_buf();
+    // every component is sanitized like tags, the origin may contain a port
+    for component in repository
+        .canonical_string()
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        path.push(version_dir_name(&component.replace(':', "_")));
+    }
+    path.push(version_dir_name(tag));
+    path.push(version_dir_name(asset_name));
+    path
+}
+
+/// Copies the downloaded asset at `downloaded` into the cache.
+///
+/// The copy is written next to its destination and renamed, so the cache
+/// never contains partial files.
+pub fn cache_asset(downloaded: &Path, cache_path: &Path) -> io::Result<()> {
+    let parent = cache_path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let file_name = cache_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary = parent.join(format!(".{file_name}.grd-tmp"));
+    fs::copy(downloaded, &temporary)?;
+    if let Err(e) = fs::rename(&temporary, cache_path) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Marks the cached asset as used, the garbage collection removes the
+/// entries used longest ago first.
+pub fn touch_cached_asset(cache_path: &Path) -> io::Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(cache_path)?
+        .set_modified(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_repository, GitWebsite, IpType};
+
+    #[test]
+    fn test_cache_path() {
+        let repository = parse_repository(
+            "https://git.example.com:3000/group/owner/repo".to_string(),
+            GitWebsite::Gitea,
+            IpType::Any,
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            cache_path(
+                Path::new("cache"),
+                &repository,
+                "release/1.0",
+                "tool.tar.gz"
+            ),
+            Path::new("cache/git.example.com_3000/group/owner/repo/release%2F1.0/tool.tar.gz")
+        );
+    }
+}
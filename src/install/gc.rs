@@ -0,0 +1,336 @@
+//! Garbage collection of old versions and cached downloads.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::{version_dir_name, Receipt};
+use crate::Error;
+
+/// A file or directory removed (or to be removed) by the garbage collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removed {
+    pub path: PathBuf,
+    /// The space it took up, in bytes.
+    pub size: u64,
+}
+
+/// When cached downloads are removed.
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy {
+    /// Entries not used for longer are removed.
+    pub max_age: Option<Duration>,
+    /// Entries used longest ago are removed until the cache is not larger.
+    pub max_size: Option<u64>,
+}
+
+/// Total size of the files in `path`, symlinks are not followed.
+pub fn disk_usage(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += disk_usage(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Removes all but the `keep` most recently installed versions of the tool,
+/// the active version is never removed.
+///
+/// Each version is dropped from the receipt, which is saved to `state_dir`
+/// before its directory is removed, so the receipt never lists a removed
+/// version, even if removing a later one fails. With `dry_run` nothing is changed.
+pub fn remove_old_versions(
+    receipt: &mut Receipt,
+    keep: usize,
+    dry_run: bool,
+    state_dir: &Path,
+) -> Result<Vec<Removed>, Error> {
+    // tools installed without versions only have the active one
+    let Some(tool_dir) = receipt.tool_dir.clone() else {
+        return Ok(vec![]);
+    };
+    let first_kept = receipt.versions.len().saturating_sub(keep);
+    let old_tags: Vec<String> = receipt.versions[..first_kept]
+        .iter()
+        .map(|version| version.tag.clone())
+        .filter(|tag| *tag != receipt.version.tag)
+        .collect();
+
+    let mut removed = vec![];
+    for tag in old_tags {
+        let path = tool_dir.join(version_dir_name(&tag));
+        // the directory may have been removed by hand
+        let size = disk_usage(&path).unwrap_or(0);
+        if !dry_run {
+            receipt.versions.retain(|version| version.tag != tag);
+            receipt.save(state_dir)?;
+            if path.exists() {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        removed.push(Removed { path, size });
+    }
+    Ok(removed)
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+fn collect_cache_entries(dir: &Path, entries: &mut Vec<CacheEntry>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_cache_entries(&entry.path(), entries)?;
+        } else {
+            entries.push(CacheEntry {
+                path: entry.path(),
+                size: metadata.len(),
+                last_used: metadata.modified()?,
+            });
+        }
+    }
+    Ok(())
+}
+
+// removes the directories left empty, but not `dir` itself
+fn remove_empty_dirs(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+            if fs::read_dir(entry.path())?.next().is_none() {
+                fs::remove_dir(entry.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes cached downloads according to `policy`, `now` is the time their
+/// age is measured at. With `dry_run` nothing is removed.
+pub fn clean_cache(
+    cache_dir: &Path,
+    policy: &CachePolicy,
+    now: SystemTime,
+    dry_run: bool,
+) -> Result<Vec<Removed>, Error> {
+    let mut entries = vec![];
+    match collect_cache_entries(cache_dir, &mut entries) {
+        Ok(()) => {}
+        // nothing has been cached yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    }
+    // the entries used longest ago come first
+    entries.sort_by_key(|entry| entry.last_used);
+
+    let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut removed = vec![];
+    for entry in entries {
+        let too_old = policy.max_age.is_some_and(|max_age| {
+            now.duration_since(entry.last_used)
+                .is_ok_and(|age| age > max_age)
+        });
+        let too_large = policy
+            .max_size
+            .is_some_and(|max_size| total_size > max_size);
+        if !too_old && !too_large {
+            continue;
+        }
+        if !dry_run {
+            fs::remove_file(&entry.path)?;
+        }
+        total_size -= entry.size;
+        removed.push(Removed {
+            path: entry.path,
+            size: entry.size,
+        });
+    }
+    if !dry_run {
+        remove_empty_dirs(cache_dir)?;
+    }
+    Ok(removed)
+}
+
+/// Parses durations like "30d", "12h", "90m", "45s" or "2w".
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("\"{value}\" has no unit, use s, m, h, d or w"))?;
+    let (number, unit) = value.split_at(unit_start);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("\"{value}\" is not a duration"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit \"{unit}\", use s, m, h, d or w")),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("\"{value}\" is too long"))
+}
+
+const SIZE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Parses sizes like "500M", "2GiB" or "1024" (bytes), units are powers of 1024.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("\"{value}\" is not a size"))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let exponent = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(format!("unknown unit of \"{value}\", use K, M, G or T")),
+    };
+    number
+        .checked_mul(1024u64.pow(exponent))
+        .ok_or_else(|| format!("\"{value}\" is too large"))
+}
+
+/// Formats a size in bytes for humans, e.g. "1.5 MiB".
+pub fn format_size(size: u64) -> String {
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", SIZE_UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{installed_version as version, receipt, temp_dir};
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_duration("90m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn test_parse_and_format_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500M"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1kb"), Ok(1024));
+        assert!(parse_size("1X").is_err());
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536 * 1024), "1.5 MiB");
+    }
+
+    #[test]
+    fn test_remove_old_versions_keeps_active() {
+        let dir = temp_dir("gc", "versions");
+        for tag in ["v1", "v2", "v3", "v4"] {
+            fs::create_dir_all(dir.join(tag)).unwrap();
+            fs::write(dir.join(tag).join("tool"), tag).unwrap();
+        }
+        let mut receipt = Receipt {
+            tool_dir: Some(dir.clone()),
+            pinned: true,
+            versions: ["v1", "v2", "v3", "v4"].map(version).to_vec(),
+            // rolled back to the first version
+            ..receipt("tool", version("v1"))
+        };
+
+        let state_dir = dir.join("state");
+        let removed = remove_old_versions(&mut receipt, 1, true, &state_dir).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(receipt.versions.len(), 4);
+        assert!(dir.join("v2").exists());
+        assert!(!state_dir.exists());
+
+        let removed = remove_old_versions(&mut receipt, 1, false, &state_dir).unwrap();
+        assert_eq!(
+            removed,
+            vec![
+                Removed {
+                    path: dir.join("v2"),
+                    size: 2
+                },
+                Removed {
+                    path: dir.join("v3"),
+                    size: 2
+                },
+            ]
+        );
+        assert_eq!(receipt.versions, vec![version("v1"), version("v4")]);
+        assert!(dir.join("v1").exists() && !dir.join("v3").exists());
+        // the receipt has been saved along the way
+        let saved = Receipt::load(&state_dir, "tool").unwrap().unwrap();
+        assert_eq!(saved.versions, receipt.versions);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_cache() {
+        let dir = temp_dir("gc", "cache");
+        let now = SystemTime::now();
+        let days = |days: u64| Duration::from_secs(days * 86400);
+        for (name, age) in [("old/a", 40), ("recent/b", 10), ("recent/c", 1)] {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "0123456789").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - days(age))
+                .unwrap();
+        }
+
+        let policy = CachePolicy {
+            max_age: Some(days(30)),
+            max_size: Some(15),
+        };
+        let removed = clean_cache(&dir, &policy, now, false).unwrap();
+        // "a" is too old, then "b" is the oldest entry exceeding the size
+        assert_eq!(
+            removed
+                .iter()
+                .map(|removed| removed.path.clone())
+                .collect::<Vec<_>>(),
+            vec![dir.join("old/a"), dir.join("recent/b")]
+        );
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("recent/c").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
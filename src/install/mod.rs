@@ -0,0 +1,358 @@
+//! Installing executables from downloaded release assets.
+//!
+//! The asset is downloaded into a staging directory next to the installation,
+//! the executable is located in it (extracting archives and decompressing
+//! single files) and moved into place atomically.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobMatcher};
+
+use crate::{
+    extract::{self, ArchiveFormat, Compression, ExtractOptions},
+    verify::{DigestAlgorithm, HashingReader},
+    Error,
+};
+
+mod receipt;
+pub use receipt::*;
+
+mod versions;
+pub use versions::*;
+
+mod cache;
+pub use cache::*;
+
+mod gc;
+pub use gc::*;
+
+mod uninstall;
+pub use uninstall::*;
+
+/// The directory executables are installed to by default, `~/.local/bin`.
+pub fn default_bin_dir() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(".local").join("bin"))
+}
+
+// the name must not move the executable (or receipt) out of its directory
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !(name.is_empty() || name.contains(['/', '\\']) || name == "." || name == "..")
+}
+
+/// Where and under which name an executable is installed.
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Directory the shims of the executables are created in.
+    pub bin_dir: PathBuf,
+    /// Directory the versions of every tool are kept in.
+    pub tools_dir: PathBuf,
+    /// Directory downloaded assets are cached in.
+    pub cache_dir: PathBuf,
+    /// Name of the installed executable, defaults to the name of the located file.
+    pub name: Option<String>,
+    /// Selects the executable inside an archive, matched against its path and its file name.
+    pub binary: Option<GlobMatcher>,
+}
+
+impl InstallOptions {
+    pub fn new(
+        bin_dir: PathBuf,
+        tools_dir: PathBuf,
+        cache_dir: PathBuf,
+        name: Option<String>,
+        binary_pattern: Option<&str>,
+    ) -> Result<Self, Error> {
+        if let Some(name) = &name {
+            if !is_valid_name(name) {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid executable name \"{name}\""
+                )));
+            }
+        }
+        let binary = binary_pattern
+            .map(|pattern| {
+                Glob::new(pattern)
+                    .map(|glob| glob.compile_matcher())
+                    .map_err(|e| {
+                        Error::InvalidArgument(format!("invalid binary glob \"{pattern}\": {e}"))
+                    })
+            })
+            .transpose()?;
+        Ok(Self {
+            bin_dir,
+            tools_dir,
+            cache_dir,
+            name,
+            binary,
+        })
+    }
+}
+
+/// A temporary directory, which is removed with everything in it when dropped.
+///
+/// It is created inside the directory files are installed to, so they can be
+/// renamed into place instead of being copied across file systems.
+#[derive(Debug)]
+pub struct StagingDir {
+    path: PathBuf,
+}
+
+impl StagingDir {
+    pub fn new(parent: &Path) -> io::Result<Self> {
+        fs::create_dir_all(parent)?;
+        let path = parent.join(format!(".grd-staging-{}", std::process::id()));
+        // left over from an aborted run of a process with the same id
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Locates the executable in the downloaded asset at `downloaded`.
+///
+/// Archives and packages are extracted and compressed files are decompressed
+/// into `staging`, any other asset is the executable itself.
+pub fn locate_executable(
+    downloaded: &Path,
+    asset_name: &str,
+    staging: &Path,
+    options: &InstallOptions,
+) -> Result<PathBuf, Error> {
+    if let Some(format) = ArchiveFormat::from_file_name(asset_name) {
+        let destination = staging.join("extracted");
+        let extracted =
+            extract::extract_archive(downloaded, format, &destination, &ExtractOptions::default())?;
+        return find_executable(&extracted, &destination, options);
+    }
+    if let Some((compression, name)) = Compression::from_file_name(asset_name) {
+        let out_path = staging.join("decompressed").join(name);
+        fs::create_dir_all(staging.join("decompressed"))?;
+        extract::decompress_file(downloaded, &out_path, Some(compression))?;
+        return Ok(out_path);
+    }
+    Ok(downloaded.to_path_buf())
+}
+
+fn is_executable(path: &Path, metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return true;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    path.extension().is_some_and(|extension| extension == "exe")
+}
+
+// picks the executable among the extracted files
+fn find_executable(
+    extracted: &[PathBuf],
+    root: &Path,
+    options: &InstallOptions,
+) -> Result<PathBuf, Error> {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let files = extracted
+        .iter()
+        .filter(|path| fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_file()));
+    let candidates: Vec<&PathBuf> = match &options.binary {
+        Some(binary) => files
+            .filter(|path| {
+                binary.is_match(path.strip_prefix(root).unwrap_or(path))
+                    || binary.is_match(file_name(path))
+            })
+            .collect(),
+        None => files
+            .filter(|path| fs::metadata(path).is_ok_and(|metadata| is_executable(path, &metadata)))
+            .collect(),
+    };
+
+    let candidates = match candidates.as_slice() {
+        [] => {
+            return Err(Error::Install(match &options.binary {
+                Some(binary) => format!("no file in the asset matches \"{}\"", binary.glob()),
+                None => "no executable found in the asset, select one with --binary".to_string(),
+            }))
+        }
+        [executable] => return Ok(executable.to_path_buf()),
+        candidates => candidates,
+    };
+    // several executables, e.g. a tool with helper scripts: the one
+    // with the requested name wins
+    if let Some(name) = &options.name {
+        let named: Vec<_> = candidates
+            .iter()
+            .filter(|path| file_name(path) == *name)
+            .collect();
+        if let [executable] = named.as_slice() {
+            return Ok(executable.to_path_buf());
+        }
+    }
+    let names: Vec<String> = candidates
+        .iter()
+        .map(|path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect();
+    Err(Error::Install(format!(
+        "the asset contains several executables ({}), select one with --binary",
+        names.join(", ")
+    )))
+}
+
+/// Hex encoded sha256 digest of the file at `path`.
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut reader = HashingReader::new(fs::File::open(path)?, &[DigestAlgorithm::Sha256]);
+    io::copy(&mut reader, &mut io::sink())?;
+    let (_, digests) = reader.finish();
+    Ok(digests[0].to_hex())
+}
+
+/// Installs `executable` as `bin_dir/name` with the executable bit set.
+///
+/// The file is copied next to its destination first and then renamed, so an
+/// existing (maybe running) executable is replaced atomically.
+pub fn install_executable(executable: &Path, bin_dir: &Path, name: &str) -> Result<PathBuf, Error> {
+    fs::create_dir_all(bin_dir)?;
+    let destination = bin_dir.join(name);
+    let temporary = bin_dir.join(format!(".{name}.grd-tmp"));
+    fs::copy(executable, &temporary)?;
+    extract::set_executable(&temporary)?;
+    if let Err(e) = fs::rename(&temporary, &destination) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn write_file(path: &Path, executable: bool) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, path.display().to_string()).unwrap();
+        if executable {
+            extract::set_executable(path).unwrap();
+        }
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        assert!(InstallOptions::new(
+            PathBuf::from("bin"),
+            PathBuf::from("tools"),
+            PathBuf::from("cache"),
+            Some("../tool".into()),
+            None
+        )
+        .is_err());
+        assert!(InstallOptions::new(
+            PathBuf::from("bin"),
+            PathBuf::from("tools"),
+            PathBuf::from("cache"),
+            Some("tool".into()),
+            None
+        )
+        .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_executable() {
+        let dir = temp_dir("install", "find");
+        let extracted = vec![
+            write_file(&dir.join("tool-1.0/tool"), true),
+            write_file(&dir.join("tool-1.0/README.md"), false),
+        ];
+        let options =
+            InstallOptions::new(dir.clone(), dir.clone(), dir.clone(), None, None).unwrap();
+        assert_eq!(
+            find_executable(&extracted, &dir, &options).unwrap(),
+            dir.join("tool-1.0/tool")
+        );
+
+        let mut extracted = extracted;
+        extracted.push(write_file(&dir.join("tool-1.0/helper.sh"), true));
+        assert!(matches!(
+            find_executable(&extracted, &dir, &options),
+            Err(Error::Install(_))
+        ));
+        // disambiguated by the name or a glob
+        let options = InstallOptions::new(
+            dir.clone(),
+            dir.clone(),
+            dir.clone(),
+            Some("tool".into()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            find_executable(&extracted, &dir, &options).unwrap(),
+            dir.join("tool-1.0/tool")
+        );
+        let options =
+            InstallOptions::new(dir.clone(), dir.clone(), dir.clone(), None, Some("*.sh")).unwrap();
+        assert_eq!(
+            find_executable(&extracted, &dir, &options).unwrap(),
+            dir.join("tool-1.0/helper.sh")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_install_executable_replaces_existing() {
+        let dir = temp_dir("install", "replace");
+        let bin_dir = dir.join("bin");
+        let executable = write_file(&dir.join("tool"), false);
+        write_file(&bin_dir.join("renamed"), false);
+        let installed = install_executable(&executable, &bin_dir, "renamed").unwrap();
+        assert_eq!(installed, bin_dir.join("renamed"));
+        assert_eq!(
+            fs::read_to_string(&installed).unwrap(),
+            executable.display().to_string()
+        );
+        #[cfg(unix)]
+        assert!(is_executable(
+            &installed,
+            &fs::metadata(&installed).unwrap()
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_staging_dir_is_removed() {
+        let dir = temp_dir("install", "staging");
+        let staging = StagingDir::new(&dir).unwrap();
+        let path = staging.path().to_path_buf();
+        write_file(&path.join("nested/file"), false);
+        drop(staging);
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
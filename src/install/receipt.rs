@@ -0,0 +1,243 @@
+//! Receipts record what has been installed, so installed tools can be listed
+//! (and later updated or removed) without asking the git website.
+//!
+//! Every tool has one receipt, a JSON file named after the tool in the
+//! `receipts` directory of the state directory.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{arguments::GitWebsite, Error};
+
+const RECEIPTS_DIR: &str = "receipts";
+
+/// The directory receipts are stored in by default,
+/// `$XDG_STATE_HOME/grd` or `~/.local/state/grd`.
+pub fn default_state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .filter(|state_home| !state_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".local").join("state")))
+        .map(|state_home| state_home.join("grd"))
+}
+
+/// What has been installed for a tool and where it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Name of the tool, the name of the installed executable.
+    pub name: String,
+    /// The repository in the form accepted on the command line,
+    /// e.g. "github.com/owner/repo".
+    pub repository: String,
+    pub website: GitWebsite,
+    pub asset_pattern: String,
+    #[serde(default)]
+    pub allow_prerelease: bool,
+    /// Regex the tags of the releases `update` installs have to match.
+    #[serde(default)]
+    pub tag_pattern: Option<String>,
+    /// Version requirement the releases `update` installs have to match.
+    #[serde(default)]
+    pub version_requirement: Option<String>,
+    #[serde(default)]
+    pub tag_prefix: Option<String>,
+    /// Glob selecting the executable inside the asset, if one has been passed.
+    #[serde(default)]
+    pub binary: Option<String>,
+    /// The active version.
+    #[serde(flatten)]
+    pub version: InstalledVersion,
+    /// The files of the active version, the shim in the bin directory first.
+    pub files: Vec<PathBuf>,
+    /// Directory the versions of the tool are kept in,
+    /// missing for tools installed without versions.
+    #[serde(default)]
+    pub tool_dir: Option<PathBuf>,
+    /// Whether the active version has been chosen explicitly and is kept by `update`.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Every version kept in the tool directory, oldest first.
+    #[serde(default)]
+    pub versions: Vec<InstalledVersion>,
+}
+
+/// A release of a tool that has been installed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledVersion {
+    pub tag: String,
+    pub asset: String,
+    pub url: String,
+    /// Hex encoded sha256 digest of the downloaded asset.
+    pub sha256: String,
+    /// Hex encoded sha256 digest of the installed executable, used to detect
+    /// modifications before it is removed.
+    #[serde(default)]
+    pub executable_sha256: Option<String>,
+    /// RFC 3339 timestamp (UTC) of the installation.
+    pub installed_at: String,
+}
+
+impl Receipt {
+    fn path(state_dir: &Path, name: &str) -> PathBuf {
+        state_dir.join(RECEIPTS_DIR).join(format!("{name}.json"))
+    }
+
+    /// Removes the receipt, once the tool has been uninstalled.
+    pub fn remove(&self, state_dir: &Path) -> Result<(), Error> {
+        match fs::remove_file(Self::path(state_dir, &self.name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the receipt, replacing the previous receipt of the tool.
+    pub fn save(&self, state_dir: &Path) -> Result<PathBuf, Error> {
+        let path = Self::path(state_dir, &self.name);
+        fs::create_dir_all(state_dir.join(RECEIPTS_DIR))?;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // written next to the receipt and renamed, so a receipt is never
+        // left half written
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, json + "\n")?;
+        fs::rename(&temporary, &path)?;
+        Ok(path)
+    }
+
+    /// Loads the receipt of the tool `name`, `None` if it is not installed.
+    pub fn load(state_dir: &Path, name: &str) -> Result<Option<Self>, Error> {
+        // such names are never installed, they could only point outside of the receipts
+        if !super::is_valid_name(name) {
+            return Ok(None);
+        }
+        Self::read(&Self::path(state_dir, name))
+    }
+
+    fn read(path: &Path) -> Result<Option<Self>, Error> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| Error::InvalidReceipt {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            })
+    }
+}
+
+/// Loads the receipts of all installed tools, sorted by name.
+pub fn load_receipts(state_dir: &Path) -> Result<Vec<Receipt>, Error> {
+    let entries = match fs::read_dir(state_dir.join(RECEIPTS_DIR)) {
+        Ok(entries) => entries,
+        // nothing has been installed yet
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut receipts = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        if let Some(receipt) = Receipt::read(&path)? {
+            receipts.push(receipt);
+        }
+    }
+    receipts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(receipts)
+}
+
+/// Formats `time` as RFC 3339 timestamp in UTC, e.g. "2024-02-29T13:05:00Z".
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // converts days since the epoch to a date of the proleptic gregorian calendar,
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::{installed_version, receipt, temp_path};
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1709211900)),
+            "2024-02-29T13:05:00Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951868799)),
+            "2000-02-29T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_receipts() {
+        let dir = temp_path("receipt", "save");
+        let receipt = |name| receipt(name, installed_version("v1.0.0"));
+        assert_eq!(load_receipts(&dir).unwrap(), vec![]);
+        receipt("tool").save(&dir).unwrap();
+        receipt("another").save(&dir).unwrap();
+        // saving again replaces the receipt
+        let mut updated = receipt("tool");
+        updated.version.tag = "v1.1.0".to_string();
+        updated.save(&dir).unwrap();
+
+        assert_eq!(Receipt::load(&dir, "tool").unwrap(), Some(updated.clone()));
+        assert_eq!(Receipt::load(&dir, "missing").unwrap(), None);
+        assert_eq!(Receipt::load(&dir, "../receipts/tool").unwrap(), None);
+        assert_eq!(
+            load_receipts(&dir).unwrap(),
+            vec![receipt("another"), updated]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_receipt() {
+        let dir = temp_path("receipt", "invalid");
+        fs::create_dir_all(dir.join(RECEIPTS_DIR)).unwrap();
+        fs::write(dir.join(RECEIPTS_DIR).join("tool.json"), "{}").unwrap();
+        assert!(matches!(
+            load_receipts(&dir),
+            Err(Error::InvalidReceipt { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+//! Removing installed tools, exactly the files recorded in their receipts.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{file_sha256, version_dir_name, versions::CURRENT, Receipt};
+use crate::Error;
+
+// compares the file with the digest recorded on installation,
+// files that do not exist anymore have nothing to check
+fn check_file(path: &Path, expected: Option<&str>, modified: &mut Vec<String>) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    match expected {
+        None => modified.push(format!(
+            "\"{}\" can not be checked, no digest has been recorded",
+            path.display()
+        )),
+        Some(expected) if file_sha256(path)? != expected => {
+            modified.push(format!("\"{}\" has been modified", path.display()))
+        }
+        Some(_) => {}
+    }
+    Ok(())
+}
+
+// the shim has to be the symlink created on installation
+fn check_shim(
+    shim: &Path,
+    tool_dir: &Path,
+    receipt: &Receipt,
+    modified: &mut Vec<String>,
+) -> io::Result<()> {
+    let Ok(metadata) = fs::symlink_metadata(shim) else {
+        return Ok(());
+    };
+    if !cfg!(unix) {
+        // a copy of the active executable
+        return check_file(shim, receipt.version.executable_sha256.as_deref(), modified);
+    }
+    let target = tool_dir.join(CURRENT).join(&receipt.name);
+    if !metadata.is_symlink() {
+        modified.push(format!("\"{}\" is not a symlink anymore", shim.display()));
+    } else if fs::read_link(shim)? != target {
+        modified.push(format!(
+            "\"{}\" does not point to \"{}\" anymore",
+            shim.display(),
+            target.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Describes every installed file of the tool that has been modified since
+/// its installation (or can not be checked), empty if none has.
+pub fn find_modified_files(receipt: &Receipt) -> Result<Vec<String>, Error> {
+    let mut modified = vec![];
+    let Some(tool_dir) = &receipt.tool_dir else {
+        // installed without versions, the executable is the only file
+        for file in &receipt.files {
+            check_file(
+                file,
+                receipt.version.executable_sha256.as_deref(),
+                &mut modified,
+            )?;
+        }
+        return Ok(modified);
+    };
+
+    if let Some(shim) = receipt.files.first() {
+        check_shim(shim, tool_dir, receipt, &mut modified)?;
+    }
+    for version in &receipt.versions {
+        let version_dir = tool_dir.join(version_dir_name(&version.tag));
+        let Ok(entries) = fs::read_dir(&version_dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.file_name() != Some(receipt.name.as_ref()) {
+                modified.push(format!(
+                    "\"{}\" has not been installed by grd",
+                    path.display()
+                ));
+            }
+        }
+        check_file(
+            &version_dir.join(&receipt.name),
+            version.executable_sha256.as_deref(),
+            &mut modified,
+        )?;
+    }
+    Ok(modified)
+}
+
+fn remove_if_exists(path: &Path, removed: &mut Vec<PathBuf>) -> io::Result<()> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    removed.push(path.to_path_buf());
+    Ok(())
+}
+
+/// Removes the shim, the `current` symlink and every version of the tool.
+///
+/// Unless `force` is set, nothing is removed if any of the files has been
+/// modified since its installation. The receipt itself is kept, the removed
+/// paths are returned.
+pub fn uninstall(receipt: &Receipt, force: bool) -> Result<Vec<PathBuf>, Error> {
+    if !force {
+        let modified = find_modified_files(receipt)?;
+        if !modified.is_empty() {
+            return Err(Error::Install(format!(
+                "refusing to uninstall \"{}\", pass --force to remove it anyway:\n{}",
+                receipt.name,
+                modified.join("\n")
+            )));
+        }
+    }
+
+    let mut removed = vec![];
+    let Some(tool_dir) = &receipt.tool_dir else {
+        for file in &receipt.files {
+            remove_if_exists(file, &mut removed)?;
+        }
+        return Ok(removed);
+    };
+    if let Some(shim) = receipt.files.first() {
+        remove_if_exists(shim, &mut removed)?;
+    }
+    remove_if_exists(&tool_dir.join(CURRENT), &mut removed)?;
+    for version in &receipt.versions {
+        remove_if_exists(&tool_dir.join(version_dir_name(&version.tag)), &mut removed)?;
+    }
+    // anything else in it has not been created by grd and is kept
+    if fs::remove_dir(tool_dir).is_ok() {
+        removed.push(tool_dir.clone());
+    }
+    Ok(removed)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        install::{add_version, install_version, switch_version},
+        test_util::{installed_version, receipt, temp_dir},
+    };
+
+    // installs the versions "v1" and "v2" of "tool"
+    fn install(dir: &Path) -> Receipt {
+        let tool_dir = dir.join("tools/tool");
+        let mut receipt = Receipt {
+            tool_dir: Some(tool_dir.clone()),
+            ..receipt("tool", installed_version("v1"))
+        };
+        for tag in ["v1", "v2"] {
+            let executable = dir.join(tag);
+            fs::write(&executable, tag).unwrap();
+            let installed = install_version(&executable, &tool_dir, tag, "tool").unwrap();
+            let mut version = installed_version(tag);
+            version.executable_sha256 = Some(file_sha256(&installed).unwrap());
+            add_version(&mut receipt, version);
+            switch_version(&mut receipt, tag, &dir.join("bin")).unwrap();
+        }
+        receipt
+    }
+
+    #[test]
+    fn test_uninstall() {
+        let dir = temp_dir("uninstall", "clean");
+        let receipt = install(&dir);
+        assert!(find_modified_files(&receipt).unwrap().is_empty());
+        let removed = uninstall(&receipt, false).unwrap();
+        assert_eq!(removed.len(), 5);
+        assert!(!dir.join("bin/tool").exists());
+        assert!(!dir.join("tools/tool").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_uninstall_refuses_modified_files() {
+        let dir = temp_dir("uninstall", "modified");
+        let receipt = install(&dir);
+        fs::write(dir.join("tools/tool/v1/tool"), "patched").unwrap();
+        fs::write(dir.join("tools/tool/v2/notes.txt"), "mine").unwrap();
+        assert_eq!(find_modified_files(&receipt).unwrap().len(), 2);
+        assert!(matches!(uninstall(&receipt, false), Err(Error::Install(_))));
+        assert!(dir.join("bin/tool").exists());
+
+        uninstall(&receipt, true).unwrap();
+        assert!(!dir.join("tools/tool").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
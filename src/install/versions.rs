@@ -0,0 +1,274 @@
+//! Every installed version of a tool is kept in its own directory:
+//!
+//! ```text
+//! <root>/<tool>/<tag>/<executable>
+//! <root>/<tool>/current -> <tag>
+//! <bin dir>/<executable> -> <root>/<tool>/current/<executable>
+//! ```
+//!
+//! Switching versions only replaces the `current` symlink, so going back to a
+//! previous version does not need a download.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{install_executable, InstalledVersion, Receipt};
+use crate::Error;
+
+pub(super) const CURRENT: &str = "current";
+
+/// The directory versions of tools are kept in by default,
+/// `$XDG_DATA_HOME/grd/tools` or `~/.local/share/grd/tools`.
+pub fn default_tools_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|data_home| !data_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".local").join("share")))
+        .map(|data_home| data_home.join("grd").join("tools"))
+}
+
+/// Name of the directory of the version `tag`.
+///
+/// Tags may contain slashes (e.g. "release/1.0"), which are percent-encoded
+/// along with `%` itself, so different tags never share a directory. A leading
+/// dot is encoded as well, names starting with one are used for temporary
+/// directories, and so is the first character of a tag named like `current`.
+pub fn version_dir_name(tag: &str) -> String {
+    if tag.is_empty() {
+        // a single "%" can not be the result of encoding a tag
+        return "%".to_string();
+    }
+    let mut name = String::with_capacity(tag.len());
+    for (index, character) in tag.char_indices() {
+        match character {
+            '%' | '/' | '\\' => name.push_str(&format!("%{:02X}", character as u32)),
+            '.' if index == 0 => name.push_str("%2E"),
+            _ if index == 0 && tag == CURRENT => {
+                name.push_str(&format!("%{:02X}", character as u32))
+            }
+            _ => name.push(character),
+        }
+    }
+    name
+}
+
+/// Copies `executable` into the directory of the version `tag` as `name`,
+/// replacing a previous installation of the same version.
+///
+/// Returns the path of the installed executable.
+pub fn install_version(
+    executable: &Path,
+    tool_dir: &Path,
+    tag: &str,
+    name: &str,
+) -> Result<PathBuf, Error> {
+    let version_dir = tool_dir.join(version_dir_name(tag));
+    // the version is prepared next to its final location, so it
+    // appears completely or not at all
+    let temporary = tool_dir.join(format!(".{}.grd-tmp", version_dir_name(tag)));
+    // a version installed before is moved aside until the new one is in place,
+    // so `current` only points at a missing directory between the two renames
+    let replaced = tool_dir.join(format!(".{}.grd-old", version_dir_name(tag)));
+    for leftover in [&temporary, &replaced] {
+        if leftover.exists() {
+            fs::remove_dir_all(leftover)?;
+        }
+    }
+    install_executable(executable, &temporary, name)?;
+    let is_replacing = version_dir.exists();
+    if is_replacing {
+        fs::rename(&version_dir, &replaced)?;
+    }
+    if let Err(e) = fs::rename(&temporary, &version_dir) {
+        if is_replacing {
+            let _ = fs::rename(&replaced, &version_dir);
+        }
+        return Err(e.into());
+    }
+    if is_replacing {
+        fs::remove_dir_all(&replaced)?;
+    }
+    Ok(version_dir.join(name))
+}
+
+/// The name of the version directory `current` points to.
+pub fn current_version_dir(tool_dir: &Path) -> Option<String> {
+    fs::read_link(tool_dir.join(CURRENT))
+        .ok()
+        .map(|target| target.to_string_lossy().to_string())
+}
+
+// replaces a symlink atomically by renaming a new one over it
+fn replace_symlink(target: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+    let file_name = link
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary = link.with_file_name(format!(".{file_name}.grd-tmp"));
+    let _ = fs::remove_file(&temporary);
+    create_symlink(target, &temporary, is_dir)?;
+    if let Err(e) = fs::rename(&temporary, link) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Makes the version `tag` of the tool in `receipt` the active one.
+///
+/// `current` is pointed at the version and the shim in the bin directory at
+/// `current`, the receipt is updated but not saved.
+pub fn switch_version(receipt: &mut Receipt, tag: &str, bin_dir: &Path) -> Result<(), Error> {
+    let version = receipt
+        .versions
+        .iter()
+        .find(|version| version.tag == tag)
+        .cloned()
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "version \"{tag}\" of \"{}\" is not installed",
+                receipt.name
+            ))
+        })?;
+    let tool_dir = receipt.tool_dir.clone().ok_or_else(|| {
+        Error::Install(format!(
+            "\"{}\" has been installed without versions, update it first",
+            receipt.name
+        ))
+    })?;
+    let version_dir = version_dir_name(tag);
+    let executable = tool_dir.join(&version_dir).join(&receipt.name);
+    if !executable.is_file() {
+        return Err(Error::Install(format!(
+            "the executable of version \"{tag}\" is missing (\"{}\")",
+            executable.display()
+        )));
+    }
+
+    replace_symlink(Path::new(&version_dir), &tool_dir.join(CURRENT), true)?;
+    let shim = link_shim(&tool_dir.join(CURRENT).join(&receipt.name), bin_dir)?;
+    receipt.version = version;
+    receipt.files = vec![shim, executable];
+    Ok(())
+}
+
+// the shim is a symlink to the executable in "current", on systems where
+// creating symlinks needs privileges it is a copy of the executable
+fn link_shim(executable: &Path, bin_dir: &Path) -> Result<PathBuf, Error> {
+    let name = executable
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if cfg!(unix) {
+        fs::create_dir_all(bin_dir)?;
+        let shim = bin_dir.join(name);
+        replace_symlink(executable, &shim, false)?;
+        Ok(shim)
+    } else {
+        install_executable(executable, bin_dir, &name)
+    }
+}
+
+/// Adds `version` to the versions of the tool, replacing an older
+/// installation of the same tag.
+pub fn add_version(receipt: &mut Receipt, version: InstalledVersion) {
+    receipt
+        .versions
+        .retain(|installed| installed.tag != version.tag);
+    receipt.versions.push(version);
+}
+
+/// The version installed before the active one, if any.
+pub fn previous_version(receipt: &Receipt) -> Option<&InstalledVersion> {
+    let active = receipt
+        .versions
+        .iter()
+        .position(|version| version.tag == receipt.version.tag)?;
+    active.checked_sub(1).map(|index| &receipt.versions[index])
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{installed_version as version, receipt, temp_dir};
+
+    #[test]
+    fn test_version_dir_name() {
+        assert_eq!(version_dir_name("v1.0.0"), "v1.0.0");
+        assert_eq!(version_dir_name("release/1.0"), "release%2F1.0");
+        assert_eq!(version_dir_name("release_1.0"), "release_1.0");
+        assert_eq!(version_dir_name("release%2F1.0"), "release%252F1.0");
+        assert_eq!(version_dir_name("win\\1.0"), "win%5C1.0");
+        assert_eq!(version_dir_name(""), "%");
+        assert_eq!(version_dir_name("."), "%2E");
+        assert_eq!(version_dir_name(".."), "%2E.");
+        assert_eq!(version_dir_name(".v1.grd-tmp"), "%2Ev1.grd-tmp");
+        assert_eq!(version_dir_name("current"), "%63urrent");
+        assert_eq!(version_dir_name("current/1"), "current%2F1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_switch_versions() {
+        let dir = temp_dir("versions", "switch");
+        let (tool_dir, bin_dir) = (dir.join("tools/tool"), dir.join("bin"));
+        let mut receipt = Receipt {
+            tool_dir: Some(tool_dir.clone()),
+            ..receipt("tool", version("v1"))
+        };
+        for tag in ["v1", "v2"] {
+            let executable = dir.join(tag);
+            fs::write(&executable, tag).unwrap();
+            install_version(&executable, &tool_dir, tag, "tool").unwrap();
+            add_version(&mut receipt, version(tag));
+            switch_version(&mut receipt, tag, &bin_dir).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(bin_dir.join("tool")).unwrap(), "v2");
+        assert_eq!(current_version_dir(&tool_dir).as_deref(), Some("v2"));
+        assert_eq!(receipt.files[1], tool_dir.join("v2/tool"));
+
+        let previous = previous_version(&receipt).unwrap().tag.clone();
+        switch_version(&mut receipt, &previous, &bin_dir).unwrap();
+        assert_eq!(fs::read_to_string(bin_dir.join("tool")).unwrap(), "v1");
+        assert_eq!(receipt.version, version("v1"));
+        assert!(previous_version(&receipt).is_none());
+
+        assert!(matches!(
+            switch_version(&mut receipt, "v3", &bin_dir),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // reinstalling the active version replaces it without leftovers
+        let executable = dir.join("v1-rebuilt");
+        fs::write(&executable, "v1 rebuilt").unwrap();
+        install_version(&executable, &tool_dir, "v1", "tool").unwrap();
+        assert_eq!(
+            fs::read_to_string(bin_dir.join("tool")).unwrap(),
+            "v1 rebuilt"
+        );
+        let mut entries: Vec<_> = fs::read_dir(&tool_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, ["current", "v1", "v2"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
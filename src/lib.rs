@@ -0,0 +1,669 @@
+//! Resolve and download release assets from GitHub, Gitea (and thus Forgejo) and GitLab.
+//!
+//! The `grd` binary is a thin command line interface over this library.
+
+pub mod arguments;
+pub mod config;
+mod error;
+pub mod extract;
+pub mod install;
+pub mod manifest;
+pub mod models;
+pub mod verify;
+pub mod version;
+
+#[cfg(test)]
+mod test_util;
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub use arguments::{
+    parse_repository, resolve_repository, GitWebsite, IpType, ParseRepositoryError, Repository,
+};
+pub use error::Error;
+use indicatif::{ProgressBar, ProgressStyle};
+use models::*;
+use regex::Regex;
+use ureq::{Agent, Resolver, Response};
+use verify::{Digest, DigestAlgorithm, HashingReader, HashingWriter};
+
+impl Resolver for IpType {
+    fn resolve(&self, netloc: &str) -> std::io::Result<Vec<std::net::SocketAddr>> {
+        ToSocketAddrs::to_socket_addrs(netloc).map(|iter| {
+            iter.filter(|address| match self {
+                Self::Any => true,
+                Self::IPV4 => address.is_ipv4(),
+                Self::IPV6 => address.is_ipv6(),
+            })
+            .collect()
+        })
+    }
+}
+
+/// Creates the agent requests for `repository` are sent with, using its ip
+/// type, proxy and CA bundle.
+pub fn get_default_agent(repository: &Repository) -> Result<Agent, Error> {
+    let mut builder = ureq::AgentBuilder::new().resolver(repository.ip_type);
+    if let Some(proxy) = &repository.proxy {
+        let proxy = ureq::Proxy::new(proxy)
+            .map_err(|e| Error::InvalidArgument(format!("invalid proxy \"{proxy}\": {e}")))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(ca_bundle) = &repository.ca_bundle {
+        builder = builder.tls_config(Arc::new(get_tls_config(ca_bundle)?));
+    }
+    Ok(builder.build())
+}
+
+// trusts the certificate authorities of the bundle besides the built-in ones
+fn get_tls_config(ca_bundle: &Path) -> Result<rustls::ClientConfig, Error> {
+    let invalid = |reason: String| {
+        Error::InvalidArgument(format!(
+            "invalid CA bundle \"{}\": {reason}",
+            ca_bundle.display()
+        ))
+    };
+    let pem = std::fs::read(ca_bundle).map_err(|e| invalid(e.to_string()))?;
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|anchor| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    let mut added = 0;
+    for pem in x509_parser::pem::Pem::iter_from_buffer(&pem) {
+        let pem = pem.map_err(|e| invalid(e.to_string()))?;
+        roots
+            .add(&rustls::Certificate(pem.contents))
+            .map_err(|e| invalid(e.to_string()))?;
+        added += 1;
+    }
+    if added == 0 {
+        return Err(invalid("no PEM encoded certificate found".to_string()));
+    }
+    Ok(rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+// GitHub requires the usage of a user agent
+const USERAGENT: &str = "gitweb-release-downloader";
+
+/// Returns the release with the given tag, or the latest release if `tag` is `None`.
+///
+/// Releases are expected to be ordered from newest to oldest, as returned by the APIs.
+///
+/// If `tag_pattern` is given, only releases whose tag matches it are
+/// considered, e.g. to select one of the release streams of a repository
+/// publishing tags like "cli-v1.2.0" and "server-v3.1.0". If the pattern has a
+/// capture group, it extracts the version from the tag and the release with
+/// the highest version is the latest one, tags whose version can not be parsed
+/// (see [`version::matching_version`]) are skipped then.
+pub fn find_release<'a>(
+    releases: &'a [Release],
+    tag: Option<&str>,
+    tag_pattern: Option<&Regex>,
+    allow_prerelease: bool,
+) -> Option<&'a Release> {
+    let mut candidates = releases.iter().filter(|release| {
+        // drafts are only visible to authenticated users with write access
+        // and are not meant to be consumed
+        !release.draft
+            && (!release.prerelease || allow_prerelease)
+            && tag_pattern.is_none_or(|tag_pattern| tag_pattern.is_match(&release.tag_name))
+    });
+    match (tag, tag_pattern) {
+        (Some(tag), _) => candidates.find(|release| release.tag_name == tag),
+        (None, Some(tag_pattern)) if tag_pattern.captures_len() > 1 => candidates
+            // of releases with the same version the newest one is used
+            .rev()
+            .filter_map(|release| {
+                let version = version::matching_version(
+                    &release.tag_name,
+                    &version::VersionReq::STAR,
+                    Some(tag_pattern),
+                    None,
+                    allow_prerelease,
+                )?;
+                Some((version, release))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, release)| release),
+        // if tag is latest take the first, which is
+        // the latest
+        (None, _) => candidates.next(),
+    }
+}
+
+/// Returns the first asset matching `asset_name_pattern` in the release selected by [`find_release`].
+pub fn find_asset<'a>(
+    releases: &'a [Release],
+    tag: Option<&str>,
+    allow_prerelease: bool,
+    asset_name_pattern: &Regex,
+) -> Option<&'a Asset> {
+    let release = find_release(releases, tag, None, allow_prerelease)?;
+    find_asset_in_release(release, asset_name_pattern)
+}
+
+/// Returns the first asset of `release` matching `asset_name_pattern`.
+pub fn find_asset_in_release<'a>(
+    release: &'a Release,
+    asset_name_pattern: &Regex,
+) -> Option<&'a Asset> {
+    release
+        .assets
+        .iter()
+        .find(|&asset| asset_name_pattern.is_match(&asset.name))
+}
+
+pub fn find_assets_in_release<'a>(
+    release: &'a Release,
+    asset_name_pattern: &Regex,
+) -> Vec<&'a Asset> {
+    let mut matching_assets = vec![];
+    for asset in &release.assets {
+        if asset_name_pattern.is_match(&asset.name) {
+            matching_assets.push(asset);
+        }
+    }
+    matching_assets
+}
+
+#[inline(always)]
+fn get_scheme_from_repository_string(url: &str) -> &str {
+    if url.starts_with("http://") {
+        "http"
+    } else {
+        "https"
+    }
+}
+
+fn get_releases_api_url(repository: &Repository) -> String {
+    let scheme = get_scheme_from_repository_string(&repository.passed_string);
+    match repository.website {
+        GitWebsite::GitHub => {
+            format!(
+                "{scheme}://api.github.com/repos/{owner}/{name}/releases",
+                owner = repository.owner,
+                name = repository.name,
+            )
+        }
+        GitWebsite::Gitea => format!(
+            "{scheme}://{origin}{sub_path}api/v1/repos/{owner}/{name}/releases",
+            origin = repository.origin,
+            sub_path = repository.sub_path,
+            owner = repository.owner,
+            name = repository.name
+        ),
+        GitWebsite::GitLab => format!(
+            "{scheme}://{origin}{sub_path}api/v4/projects/{owner}%2F{name}/releases",
+            origin = repository.origin,
+            sub_path = repository.sub_path,
+            owner = repository.owner,
+            name = repository.name
+        ),
+    }
+}
+
+/// Fetches the releases of `repository`, newest first.
+pub fn get_releases(agent: &Agent, repository: &Repository) -> Result<Vec<Release>, Error> {
+    let releases_address = get_releases_api_url(repository);
+
+    let response = make_get_request(agent, &releases_address, &repository.headers)?;
+
+    let releases_json_string = response
+        .into_string()
+        .map_err(|e| Error::InvalidResponse(format!("Could not get json from response:\n{e}")))?;
+
+    let releases = match repository.website {
+        GitWebsite::GitHub | GitWebsite::Gitea => {
+            serde_json::from_str::<Vec<Release>>(&releases_json_string)
+        }
+        GitWebsite::GitLab => serde_json::from_str::<Vec<GitLabRelease>>(&releases_json_string)
+            .map(|e| e.into_iter().map(Into::into).collect()),
+    };
+    releases.map_err(|e| Error::InvalidResponse(format!("Could not deserialize json:\n{e}")))
+}
+
+pub fn make_get_request(agent: &Agent, url: &str, headers: &[String]) -> Result<Response, Error> {
+    let mut request = agent.get(url).set("user-agent", USERAGENT);
+    for header in headers {
+        // according to the first paragraph of the following mdn site, whitespace before the value
+        // is ignored, so we don't need to remove anything
+        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers
+        let (header_name, value) = header
+            .split_once(":")
+            .ok_or_else(|| Error::InvalidHeader(header.clone()))?;
+        request = request.set(header_name, value);
+    }
+
+    request.call().map_err(Into::into)
+}
+
+pub fn get_content_length(response: &Response) -> Option<usize> {
+    response
+        .header("content-length")
+        .map_or_else(|| None, |input| input.parse::<usize>().ok())
+}
+
+fn create_progress_bar(content_length: usize) -> ProgressBar {
+    let pb = ProgressBar::new(content_length as u64);
+    let pb_style = ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] [{wide_bar:.green/red}] {bytes}/{total_bytes}",
+    )
+    // this hard coded template will always succeed compiling,
+    // so it's okay to unwrap
+    .unwrap()
+    .progress_chars("=>-");
+    pb.set_style(pb_style);
+    pb
+}
+
+pub fn create_and_init_progress_bar(content_length_option: Option<usize>) -> Option<ProgressBar> {
+    let content_length = content_length_option?;
+    let pb = create_progress_bar(content_length);
+    pb.set_position(0);
+    Some(pb)
+}
+
+/// Copies the body of `response` into `out_file`, updating the progress bar if one is passed.
+///
+/// Returns the number of bytes written.
+pub fn stream_response_into_file(
+    response: Response,
+    out_file: &mut impl Write,
+    pb_option: &Option<ProgressBar>,
+) -> Result<u64, Error> {
+    let mut stream = response.into_reader();
+
+    let mut bytes_downloaded = 0;
+    let mut buffer = [0_u8; 8192];
+
+    loop {
+        let read_size = stream.read(&mut buffer)?;
+        // download has finished
+        if read_size == 0 {
+            break;
+        }
+        out_file.write_all(&buffer[0..read_size])?;
+
+        bytes_downloaded += read_size as u64;
+
+        if let Some(ref pb) = pb_option {
+            pb.set_position(bytes_downloaded);
+        }
+    }
+    out_file.flush()?;
+    Ok(bytes_downloaded)
+}
+
+fn get_github_asset_api_url(owner: &str, repository: &str, asset_id: i64) -> String {
+    format!("https://api.github.com/repos/{owner}/{repository}/releases/assets/{asset_id}")
+}
+
+/// Requests the binary content of `asset`.
+pub fn get_asset_response(
+    agent: &Agent,
+    repository: &Repository,
+    asset: &Asset,
+) -> Result<Response, Error> {
+    if matches!(repository.website, GitWebsite::GitHub) {
+        let mut headers = repository.headers.clone();
+        headers.push("Accept: application/octet-stream".to_string());
+        let url = get_github_asset_api_url(&repository.owner, &repository.name, asset.id);
+        make_get_request(agent, &url, &headers)
+    } else {
+        make_get_request(agent, &asset.browser_download_url, &repository.headers)
+    }
+}
+
+/// Options for [`download_asset`].
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Draw a progress bar to stderr while downloading.
+    pub show_progress: bool,
+    /// Verify the file against the digest published by the website, if there is one.
+    pub verify_digest: bool,
+    /// Further digests the file must match, e.g. taken from a checksum file of the release.
+    pub expected_digests: Vec<Digest>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            show_progress: false,
+            verify_digest: true,
+            expected_digests: vec![],
+        }
+    }
+}
+
+/// Information about a file written by [`download_asset`].
+#[derive(Debug, Clone)]
+pub struct DownloadedAsset {
+    pub size: u64,
+    pub sha256: Digest,
+    /// The digests the file has been verified against.
+    pub verified_digests: Vec<Digest>,
+}
+
+// returns the digests the asset has to match and the algorithms to compute
+fn get_expected_digests(
+    asset: &Asset,
+    options: &DownloadOptions,
+) -> (Vec<Digest>, Vec<DigestAlgorithm>) {
+    let mut expected_digests = options.expected_digests.clone();
+    if options.verify_digest {
+        if let Some(digest) = asset.digest.as_deref().and_then(|d| d.parse().ok()) {
+            expected_digests.push(digest);
+        }
+    }
+    // sha256 is always computed, since it is the most commonly published digest
+    let mut algorithms = vec![DigestAlgorithm::Sha256];
+    for digest in &expected_digests {
+        if !algorithms.contains(&digest.algorithm) {
+            algorithms.push(digest.algorithm);
+        }
+    }
+    (expected_digests, algorithms)
+}
+
+/// Downloads `asset` into a newly created file at `out_path`.
+///
+/// The content is hashed while it is written. If the website publishes a digest for
+/// the asset and [`DownloadOptions::verify_digest`] is set, it is checked along with
+/// [`DownloadOptions::expected_digests`]. On a mismatch the file is deleted and
+/// [`Error::Verification`] is returned. Published digests using unsupported
+/// algorithms are ignored.
+pub fn download_asset(
+    agent: &Agent,
+    repository: &Repository,
+    asset: &Asset,
+    out_path: &Path,
+    options: &DownloadOptions,
+) -> Result<DownloadedAsset, Error> {
+    let response = get_asset_response(agent, repository, asset)?;
+    write_asset_response(response, asset, out_path, options)
+}
+
+/// Downloads `asset` from its `browser_download_url` instead of the API of the
+/// website, e.g. the url recorded in a lockfile, and verifies it like
+/// [`download_asset`].
+pub fn download_asset_from_url(
+    agent: &Agent,
+    repository: &Repository,
+    asset: &Asset,
+    out_path: &Path,
+    options: &DownloadOptions,
+) -> Result<DownloadedAsset, Error> {
+    let response = make_get_request(agent, &asset.browser_download_url, &repository.headers)?;
+    write_asset_response(response, asset, out_path, options)
+}
+
+fn write_asset_response(
+    response: Response,
+    asset: &Asset,
+    out_path: &Path,
+    options: &DownloadOptions,
+) -> Result<DownloadedAsset, Error> {
+    let (expected_digests, algorithms) = get_expected_digests(asset, options);
+
+    let out_file = File::create(out_path)?;
+    let mut writer = HashingWriter::new(out_file, &algorithms);
+
+    let pb_option = if options.show_progress {
+        create_and_init_progress_bar(get_content_length(&response))
+    } else {
+        None
+    };
+
+    let size = stream_response_into_file(response, &mut writer, &pb_option)?;
+
+    if let Some(ref pb) = pb_option {
+        pb.finish();
+        eprintln!();
+    }
+
+    let (_, mut digests) = writer.finish();
+
+    if let Err(reason) = verify::check_digests(&digests, &expected_digests) {
+        // a corrupted or tampered file must not be left behind
+        std::fs::remove_file(out_path)?;
+        return Err(Error::Verification {
+            asset: asset.name.clone(),
+            reason,
+        });
+    }
+
+    Ok(DownloadedAsset {
+        size,
+        // sha256 is always the first algorithm
+        sha256: digests.swap_remove(0),
+        verified_digests: expected_digests,
+    })
+}
+
+/// Verifies a local copy of `asset` at `path`, e.g. one downloaded earlier, like
+/// [`download_asset`] verifies a download.
+///
+/// Unlike [`download_asset`], the file is kept on a mismatch.
+pub fn verify_local_asset(
+    asset: &Asset,
+    path: &Path,
+    options: &DownloadOptions,
+) -> Result<DownloadedAsset, Error> {
+    let (expected_digests, algorithms) = get_expected_digests(asset, options);
+
+    let mut reader = HashingReader::new(File::open(path)?, &algorithms);
+    let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+    let (_, mut digests) = reader.finish();
+
+    verify::check_digests(&digests, &expected_digests).map_err(|reason| Error::Verification {
+        asset: asset.name.clone(),
+        reason,
+    })?;
+    Ok(DownloadedAsset {
+        size,
+        // sha256 is always the first algorithm
+        sha256: digests.swap_remove(0),
+        verified_digests: expected_digests,
+    })
+}
+
+/// Information about an archive extracted by [`download_and_extract_asset`].
+#[derive(Debug, Clone)]
+pub struct ExtractedAsset {
+    /// Size and digests of the archive itself.
+    pub download: DownloadedAsset,
+    /// The extracted files and symlinks.
+    pub extracted: Vec<PathBuf>,
+}
+
+/// Downloads `asset` and extracts it into `destination` while it is being
+/// downloaded, the archive itself is never written to disk.
+///
+/// The raw archive is hashed and verified like in [`download_asset`]. Since the
+/// digests are only known once the whole archive has been read, the extracted
+/// files are removed again if the verification fails.
+pub fn download_and_extract_asset(
+    agent: &Agent,
+    repository: &Repository,
+    asset: &Asset,
+    format: extract::ArchiveFormat,
+    destination: &Path,
+    extract_options: &extract::ExtractOptions,
+    options: &DownloadOptions,
+) -> Result<ExtractedAsset, Error> {
+    let (expected_digests, algorithms) = get_expected_digests(asset, options);
+
+    let response = get_asset_response(agent, repository, asset)?;
+
+    let pb_option = if options.show_progress {
+        create_and_init_progress_bar(get_content_length(&response))
+    } else {
+        None
+    };
+
+    let mut hashing_reader = HashingReader::new(response.into_reader(), &algorithms);
+    let mut reader: Box<dyn Read> = match &pb_option {
+        Some(pb) => Box::new(pb.wrap_read(&mut hashing_reader)),
+        None => Box::new(&mut hashing_reader),
+    };
+    let extracted = extract::extract_stream(
+        &mut reader,
+        format,
+        &asset.name,
+        destination,
+        extract_options,
+    )?;
+    // the decompressor stops at the end of the tar archive, the rest
+    // (padding or trailing data) still has to be hashed
+    if let Err(e) = std::io::copy(&mut reader, &mut std::io::sink()) {
+        // without the whole archive the extracted files can not be verified
+        let _ = extracted.remove();
+        return Err(e.into());
+    }
+    drop(reader);
+
+    if let Some(ref pb) = pb_option {
+        pb.finish();
+        eprintln!();
+    }
+
+    let size = hashing_reader.bytes_read();
+    let (_, mut digests) = hashing_reader.finish();
+
+    if let Err(reason) = verify::check_digests(&digests, &expected_digests) {
+        // the content of a corrupted or tampered archive must not be left behind
+        extracted.remove()?;
+        return Err(Error::Verification {
+            asset: asset.name.clone(),
+            reason,
+        });
+    }
+
+    Ok(ExtractedAsset {
+        download: DownloadedAsset {
+            size,
+            // sha256 is always the first algorithm
+            sha256: digests.swap_remove(0),
+            verified_digests: expected_digests,
+        },
+        extracted: extracted.files,
+    })
+}
+
+/// Downloads `asset` into memory, meant for small files like checksums or signatures.
+pub fn download_asset_bytes(
+    agent: &Agent,
+    repository: &Repository,
+    asset: &Asset,
+) -> Result<Vec<u8>, Error> {
+    let response = get_asset_response(agent, repository, asset)?;
+    let mut bytes = vec![];
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{release, write_temp_file};
+
+    #[test]
+    fn test_find_release_latest_skips_prerelease() {
+        let releases = vec![
+            release("v2.0.0-rc1", true, &[]),
+            release("v1.0.0", false, &[]),
+        ];
+        let found = find_release(&releases, None, None, false).unwrap();
+        assert_eq!(found.tag_name, "v1.0.0");
+        let found = find_release(&releases, None, None, true).unwrap();
+        assert_eq!(found.tag_name, "v2.0.0-rc1");
+    }
+
+    #[test]
+    fn test_find_release_skips_drafts() {
+        let mut draft = release("v2.0.0", false, &[]);
+        draft.draft = true;
+        let releases = vec![draft, release("v1.0.0", false, &[])];
+        let found = find_release(&releases, None, None, true).unwrap();
+        assert_eq!(found.tag_name, "v1.0.0");
+    }
+
+    #[test]
+    fn test_find_release_by_tag() {
+        let releases = vec![release("v2.0.0", false, &[]), release("v1.0.0", false, &[])];
+        let found = find_release(&releases, Some("v1.0.0"), None, false).unwrap();
+        assert_eq!(found.tag_name, "v1.0.0");
+        assert!(find_release(&releases, Some("v3.0.0"), None, false).is_none());
+    }
+
+    #[test]
+    fn test_find_release_by_tag_pattern() {
+        let releases = vec![
+            release("server-v3.1.0", false, &[]),
+            release("cli-v1.2.0", false, &[]),
+            release("cli-v1.10.0", false, &[]),
+            release("cli-v2.0.0-rc1", true, &[]),
+        ];
+        let tag_pattern = Regex::new("^cli-").unwrap();
+        let found = find_release(&releases, None, Some(&tag_pattern), false).unwrap();
+        assert_eq!(found.tag_name, "cli-v1.2.0");
+        // the version in the capture group is used for sorting
+        let tag_pattern = Regex::new("^cli-(.+)$").unwrap();
+        let found = find_release(&releases, None, Some(&tag_pattern), false).unwrap();
+        assert_eq!(found.tag_name, "cli-v1.10.0");
+        let found = find_release(&releases, None, Some(&tag_pattern), true).unwrap();
+        assert_eq!(found.tag_name, "cli-v2.0.0-rc1");
+        let found = find_release(&releases, Some("cli-v1.2.0"), Some(&tag_pattern), false);
+        assert_eq!(found.unwrap().tag_name, "cli-v1.2.0");
+        assert!(
+            find_release(&releases, Some("server-v3.1.0"), Some(&tag_pattern), false).is_none()
+        );
+    }
+
+    #[test]
+    fn test_find_asset() {
+        let releases = vec![release(
+            "v1.0.0",
+            false,
+            &["tool.tar.gz", "tool.deb", "tool.rpm"],
+        )];
+        let pattern = Regex::new(r"\.deb$").unwrap();
+        let asset = find_asset(&releases, None, false, &pattern).unwrap();
+        assert_eq!(asset.name, "tool.deb");
+        let pattern = Regex::new(r"\.zip$").unwrap();
+        assert!(find_asset(&releases, None, false, &pattern).is_none());
+    }
+
+    #[test]
+    fn test_verify_local_asset() {
+        let path = write_temp_file("lib", "local", b"hello\n");
+        let mut release = release("v1.0.0", false, &["hello.txt"]);
+        let asset = &mut release.assets[0];
+        asset.digest = Some(
+            "sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03".to_string(),
+        );
+        let verified = verify_local_asset(asset, &path, &DownloadOptions::default()).unwrap();
+        assert_eq!(verified.size, 6);
+        assert_eq!(verified.verified_digests.len(), 1);
+
+        asset.digest = Some(format!("sha256:{}", "00".repeat(32)));
+        let result = verify_local_asset(asset, &path, &DownloadOptions::default());
+        assert!(matches!(result, Err(Error::Verification { .. })));
+        // the file is kept
+        assert!(path.exists());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,397 +1,1531 @@
-mod arguments;
-mod models;
 use std::{
-    fs::File,
-    io::{stderr, Write},
-    net::ToSocketAddrs,
-    process::{self, exit},
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+    time::SystemTime,
 };
 
-use arguments::{GitWebsite, IpType};
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
-use models::*;
+use clap::{CommandFactory, FromArgMatches};
+use gitweb_release_downloader::{
+    arguments, config, download_and_extract_asset, download_asset, download_asset_from_url,
+    extract, find_asset_in_release, find_assets_in_release, find_release, get_default_agent,
+    get_releases, install, manifest,
+    models::{Asset, Release},
+    verify, verify_local_asset, version, DownloadOptions, DownloadedAsset, Error, Repository,
+};
 use regex::Regex;
-use ureq::{Agent, Resolver, Response};
-
-impl Resolver for IpType {
-    fn resolve(&self, netloc: &str) -> std::io::Result<Vec<std::net::SocketAddr>> {
-        ToSocketAddrs::to_socket_addrs(netloc).map(|iter| {
-            iter.filter(|address| match self {
-                Self::Any => true,
-                Self::IPV4 => address.is_ipv4(),
-                Self::IPV6 => address.is_ipv6(),
-            })
-            .collect()
-        })
-    }
+use ureq::Agent;
+
+// missing values are printed as "-", so the columns of the long output stay aligned
+fn optional_to_string(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
 }
 
-fn get_default_agent(repository: &arguments::Repository) -> Agent {
-    ureq::AgentBuilder::new()
-        .resolver(repository.ip_type)
-        .build()
+fn print_releases(releases_query_args: arguments::ReleasesQueryArgs) -> Result<(), Error> {
+    let tag_pattern = releases_query_args
+        .tag_pattern
+        .as_deref()
+        .map(Regex::new)
+        .transpose()?;
+    let requirement = releases_query_args
+        .version_requirement
+        .as_deref()
+        .map(version::parse_requirement)
+        .transpose()?;
+    let agent: Agent = get_default_agent(&releases_query_args.repository)?;
+
+    let repository = releases_query_args.repository;
+    let releases = get_releases(&agent, &repository)?;
+    let mut releases: Vec<&Release> = releases
+        .iter()
+        .filter(|release| !release.draft)
+        .filter(|release| !release.prerelease || releases_query_args.allow_prerelease)
+        .filter(|release| {
+            tag_pattern
+                .as_ref()
+                .is_none_or(|tag_pattern| tag_pattern.is_match(&release.tag_name))
+        })
+        .collect();
+    // like when selecting a release, a version requirement or a tag pattern
+    // extracting the version makes the highest version the latest
+    let requirement = requirement.or_else(|| {
+        tag_pattern
+            .as_ref()
+            .filter(|tag_pattern| tag_pattern.captures_len() > 1)
+            .map(|_| version::VersionReq::STAR)
+    });
+    if let Some(requirement) = requirement {
+        let mut matching: Vec<_> = releases
+            .into_iter()
+            .filter_map(|release| {
+                let version = version::matching_version(
+                    &release.tag_name,
+                    &requirement,
+                    tag_pattern.as_ref(),
+                    releases_query_args.tag_prefix.as_deref(),
+                    releases_query_args.allow_prerelease,
+                )?;
+                Some((version, release))
+            })
+            .collect();
+        // the stable sort keeps newer releases of the same version first
+        matching.sort_by(|(a, _), (b, _)| b.cmp(a));
+        releases = matching.into_iter().map(|(_, release)| release).collect();
+    }
+    for release in releases.into_iter().take(releases_query_args.count.into()) {
+        if releases_query_args.long {
+            println!(
+                "{}\t{}\t{}\t{}",
+                release.tag_name,
+                release.name.as_deref().unwrap_or("-"),
+                release.published_at.as_deref().unwrap_or("-"),
+                if release.prerelease {
+                    "prerelease"
+                } else {
+                    "release"
+                },
+            );
+        } else {
+            println!("{}", release.tag_name);
+        }
+    }
+    Ok(())
 }
 
-// GitHub requires the usage of a user agent
-const USERAGENT: &str = "gitweb-release-downloader";
+fn print_assets(assets_query_args: arguments::AssetsQueryArgs) -> Result<(), Error> {
+    let agent: Agent = get_default_agent(&assets_query_args.repository)?;
 
-fn find_release<'a>(
-    releases: &'a [Release],
-    tag: Option<&str>,
-    allow_prerelease: bool,
-) -> Option<&'a Release> {
-    for release in releases {
-        if release.prerelease && !allow_prerelease {
-            continue;
+    let releases = get_releases(&agent, &assets_query_args.repository)?;
+    // if no tag is specified, prereleases are only allowed with --prerelease
+    // however if a tag is specified, the user explictly chose
+    // a tag that might be a prerelease, so in this case it
+    // will be allowed
+    let allow_prerelease = assets_query_args.allow_prerelease || assets_query_args.tag.is_some();
+    let release = find_release_by_tag_or_version(
+        &releases,
+        assets_query_args.tag.as_deref(),
+        assets_query_args.tag_pattern.as_deref(),
+        assets_query_args.version_requirement.as_deref(),
+        assets_query_args.tag_prefix.as_deref(),
+        allow_prerelease,
+    )?;
+    let regex = Regex::new(&assets_query_args.pattern)?;
+    let assets = find_assets_in_release(release, &regex);
+    for asset in assets {
+        if assets_query_args.long {
+            println!(
+                "{}\t{}\t{}\t{}",
+                asset.name,
+                optional_to_string(asset.size),
+                optional_to_string(asset.download_count),
+                asset.content_type.as_deref().unwrap_or("-"),
+            );
+        } else {
+            println!("{}", asset.name);
         }
-        // if tag is latest take the first, which is
-        // the latest
-        match tag {
-            None => return Some(release),
-            Some(tag) => {
-                if release.tag_name == tag {
-                    return Some(release);
-                }
-            }
+    }
+    Ok(())
+}
+
+fn get_signature_verifiers(
+    verify_args: &arguments::VerifyArgs,
+) -> Result<Vec<verify::SignatureVerifier>, Error> {
+    let mut verifiers = vec![];
+    if let Some(key) = &verify_args.minisign_key {
+        let key = verify::MinisignPublicKey::parse(key).map_err(Error::InvalidArgument)?;
+        verifiers.push(verify::SignatureVerifier::Minisign(key));
+    }
+    if let Some(keyring_path) = &verify_args.pgp_keyring {
+        let mut keyring =
+            verify::PgpKeyring::from_file(keyring_path).map_err(Error::InvalidArgument)?;
+        if !verify_args.pgp_fingerprints.is_empty() {
+            keyring
+                .pin(&verify_args.pgp_fingerprints)
+                .map_err(Error::InvalidArgument)?;
         }
+        verifiers.push(verify::SignatureVerifier::OpenPgp(keyring));
+    }
+    if let Some(trust_root_path) = &verify_args.sigstore_trust_root {
+        let trust_root = verify::SigstoreTrustRoot::from_file(trust_root_path)
+            .map_err(Error::InvalidArgument)?;
+        let identity = verify_args
+            .sigstore_identity
+            .as_deref()
+            .map(Regex::new)
+            .transpose()?;
+        let bundle_pattern = verify_args
+            .sigstore_bundle
+            .as_deref()
+            .map(Regex::new)
+            .transpose()?;
+        verifiers.push(verify::SignatureVerifier::Sigstore(
+            verify::SigstoreVerifier {
+                trust_root,
+                identity,
+                issuer: verify_args.sigstore_issuer.clone(),
+                bundle_pattern,
+            },
+        ));
     }
-    None
+    Ok(verifiers)
 }
 
-fn find_asset<'a>(
-    releases: &'a [Release],
+// a version requirement selects the release with the highest matching
+// version, otherwise the tag or the latest release is used
+fn find_release_by_tag_or_version<'r>(
+    releases: &'r [Release],
     tag: Option<&str>,
+    tag_pattern: Option<&str>,
+    version_requirement: Option<&str>,
+    tag_prefix: Option<&str>,
     allow_prerelease: bool,
-    asset_name_pattern: &Regex,
-) -> Option<&'a Asset> {
-    let release = find_release(releases, tag, allow_prerelease)?;
-    release
-        .assets
-        .iter()
-        .find(|&asset| asset_name_pattern.is_match(&asset.name))
+) -> Result<&'r Release, Error> {
+    let compiled_tag_pattern = tag_pattern.map(Regex::new).transpose()?;
+    match (version_requirement, tag, tag_pattern) {
+        (Some(version_requirement), _, _) => {
+            let requirement = version::parse_requirement(version_requirement)?;
+            version::find_release_by_version(
+                releases,
+                &requirement,
+                compiled_tag_pattern.as_ref(),
+                tag_prefix,
+                allow_prerelease,
+            )
+            .ok_or_else(|| Error::NoMatchingVersion {
+                requirement: version_requirement.to_string(),
+            })
+        }
+        (None, tag, tag_pattern) => find_release(
+            releases,
+            tag,
+            compiled_tag_pattern.as_ref(),
+            allow_prerelease,
+        )
+        .ok_or_else(|| match (tag, tag_pattern) {
+            (None, Some(tag_pattern)) => Error::NoMatchingTag {
+                pattern: tag_pattern.to_string(),
+            },
+            (tag, _) => Error::ReleaseNotFound {
+                tag: tag.map(str::to_string),
+            },
+        }),
+    }
 }
 
-fn find_assets_in_release<'a>(release: &'a Release, asset_name_pattern: &Regex) -> Vec<&'a Asset> {
-    let mut matching_assets = vec![];
-    for asset in &release.assets {
-        if asset_name_pattern.is_match(&asset.name) {
-            matching_assets.push(asset);
+fn find_release_and_asset<'r>(
+    releases: &'r [Release],
+    asset_args: &arguments::AssetArgs,
+) -> Result<(&'r Release, &'r Asset), Error> {
+    let compiled_asset_pattern = Regex::new(&asset_args.asset_pattern)?;
+    let release = find_release_by_tag_or_version(
+        releases,
+        asset_args.tag.as_deref(),
+        asset_args.tag_pattern.as_deref(),
+        asset_args.version_requirement.as_deref(),
+        asset_args.tag_prefix.as_deref(),
+        asset_args.allow_prerelease,
+    )?;
+    let asset = find_asset_in_release(release, &compiled_asset_pattern).ok_or_else(|| {
+        Error::NoMatchingAsset {
+            pattern: asset_args.asset_pattern.clone(),
+            // the tag the version requirement or tag pattern has been resolved to
+            tag: (asset_args.version_requirement.is_some() || asset_args.tag_pattern.is_some())
+                .then(|| release.tag_name.clone())
+                .or_else(|| asset_args.tag.clone()),
+            repository: asset_args.repository.passed_string.clone(),
         }
-    }
-    matching_assets
+    })?;
+    Ok((release, asset))
 }
 
-#[inline(always)]
-fn get_scheme_from_repository_string(url: &str) -> &str {
-    if url.starts_with("http://") {
-        "http"
-    } else {
-        "https"
+// fetches the checksum the download has to match, if requested
+fn get_download_options(
+    verify_args: &arguments::VerifyArgs,
+    agent: &Agent,
+    repository: &Repository,
+    release: &Release,
+    asset: &Asset,
+) -> Result<DownloadOptions, Error> {
+    let mut expected_digests = vec![];
+    if let Some(checksums_pattern) = &verify_args.verify_checksums {
+        let checksums_pattern = checksums_pattern.as_deref().map(Regex::new).transpose()?;
+        let (checksum_asset, digest) = verify::fetch_checksum(
+            agent,
+            repository,
+            release,
+            asset,
+            checksums_pattern.as_ref(),
+        )?;
+        eprintln!(r#"Using checksum from "{}""#, checksum_asset.name);
+        expected_digests.push(digest);
     }
+
+    Ok(DownloadOptions {
+        show_progress: true,
+        verify_digest: !verify_args.no_verify,
+        expected_digests,
+    })
 }
 
-fn get_releases_api_url(repository: &arguments::Repository) -> String {
-    let scheme = get_scheme_from_repository_string(&repository.passed_string);
-    match repository.website {
-        arguments::GitWebsite::GitHub => {
-            format!(
-                "{scheme}://api.github.com/repos/{owner}/{name}/releases",
-                owner = repository.owner,
-                name = repository.name,
-            )
-        }
-        arguments::GitWebsite::Gitea => format!(
-            "{scheme}://{origin}{sub_path}api/v1/repos/{owner}/{name}/releases",
-            origin = repository.origin,
-            sub_path = repository.sub_path,
-            owner = repository.owner,
-            name = repository.name
-        ),
-        arguments::GitWebsite::GitLab => format!(
-            "{scheme}://{origin}{sub_path}api/v4/projects/{owner}%2F{name}/releases",
-            origin = repository.origin,
-            sub_path = repository.sub_path,
-            owner = repository.owner,
-            name = repository.name
-        ),
-    }
-}
-
-fn get_releases(agent: &Agent, repository: &arguments::Repository) -> Vec<Release> {
-    let releases_address = get_releases_api_url(repository);
-
-    let response =
-        make_get_request(agent, &releases_address, &repository.headers).unwrap_or_else(|e| {
-            eprintln!("HTTP request failed:\n{e}");
-            process::exit(1);
-        });
+// downloads the asset to out_path and verifies its digests and signatures
+fn download_verified_asset(
+    agent: &Agent,
+    repository: &Repository,
+    release: &Release,
+    asset: &Asset,
+    signature_verifiers: &[verify::SignatureVerifier],
+    options: &DownloadOptions,
+    out_path: &Path,
+) -> Result<DownloadedAsset, Error> {
+    let signatures =
+        verify::fetch_signatures(agent, repository, release, asset, signature_verifiers)?;
 
-    let releases_json_string = response.into_string().unwrap_or_else(|e| {
-        eprintln!("Could not get json from response:\n{e}");
-        process::exit(1);
-    });
+    // printing to stderr, since posix (or unix?)
+    // says progress is written to stderr
+    // this makes sense especially if we pipe the name
+    // into a script: the script gets the downloaded
+    // file name and the user can still see the progress
+    eprintln!(r#"Downloading "{}""#, &asset.name);
 
-    let releases = match repository.website {
-        arguments::GitWebsite::GitHub | arguments::GitWebsite::Gitea => {
-            serde_json::from_str::<Vec<Release>>(&releases_json_string)
-        }
-        arguments::GitWebsite::GitLab => {
-            serde_json::from_str::<Vec<GitLabRelease>>(&releases_json_string)
-                .map(|e| e.into_iter().map(Into::into).collect())
-        }
-    };
-    releases.unwrap_or_else(|e| {
-        eprintln!("Could not deserialize json:\n{e}");
-        process::exit(1);
-    })
+    eprintln!("Writing to file \"{}\"", out_path.display());
+    let downloaded = download_asset(agent, repository, asset, out_path, options)?;
+    for digest in &downloaded.verified_digests {
+        eprintln!("Verified {} digest", digest.algorithm.name());
+    }
+
+    for description in verify::verify_signatures(&signatures, out_path, &asset.name)? {
+        eprintln!("Verified {description}");
+    }
+    Ok(downloaded)
 }
 
-fn get_compiled_asset_pattern_or_exit(pattern: &str) -> Regex {
-    Regex::new(pattern).unwrap_or_else(|e| {
-        eprintln!("Could not compile RegEx:\n{e}");
-        process::exit(1);
-    })
+fn get_extract_destination(download_args: &arguments::DownloadArgs) -> PathBuf {
+    download_args
+        .extract
+        .clone()
+        .flatten()
+        .or_else(|| download_args.output_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("."))
 }
 
-fn get_asset_or_exit<'a>(
-    releases: &'a [Release],
-    parsed_args: &arguments::DownloadArgs,
-    compiled_asset_pattern: &Regex,
-) -> &'a Asset {
-    let asset_option = find_asset(
-        releases,
-        parsed_args.tag.as_deref(),
-        parsed_args.allow_prerelease,
-        compiled_asset_pattern,
+fn stream_extract_asset(
+    download_args: &arguments::DownloadArgs,
+    agent: &Agent,
+    asset: &Asset,
+    archive_format: extract::ArchiveFormat,
+    extract_options: &extract::ExtractOptions,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    let destination = get_extract_destination(download_args);
+
+    eprintln!(
+        r#"Downloading and extracting "{}" to "{}""#,
+        &asset.name,
+        destination.display()
     );
+    let extracted = download_and_extract_asset(
+        agent,
+        &download_args.asset.repository,
+        asset,
+        archive_format,
+        &destination,
+        extract_options,
+        options,
+    )?;
+    for digest in extracted.download.verified_digests {
+        eprintln!("Verified {} digest", digest.algorithm.name());
+    }
+    eprintln!(
+        r#"Extracted {} files to "{}""#,
+        extracted.extracted.len(),
+        destination.display()
+    );
+    if download_args.print_filename {
+        print!("{}", destination.display())
+    }
+    Ok(())
+}
 
-    let Some(asset) = asset_option else {
-        let tag_string = match &parsed_args.tag {
-            Some(tag) => format!("tag \"{tag}\""),
-            None => "latest tag".to_string(),
-        };
+fn download_assets(download_args: arguments::DownloadArgs) -> Result<(), Error> {
+    // the arguments are checked before any request is sent
+    Regex::new(&download_args.asset.asset_pattern)?;
+    if let Some(tag_pattern) = &download_args.asset.tag_pattern {
+        Regex::new(tag_pattern)?;
+    }
+    if let Some(version_requirement) = &download_args.asset.version_requirement {
+        version::parse_requirement(version_requirement)?;
+    }
+    let signature_verifiers = get_signature_verifiers(&download_args.verify)?;
+    let extract_options = download_args
+        .extract
+        .is_some()
+        .then(|| {
+            extract::ExtractOptions::new(download_args.strip_components, &download_args.include)
+        })
+        .transpose()?;
+
+    let repository = &download_args.asset.repository;
+    let agent: Agent = get_default_agent(repository)?;
+    let releases = get_releases(&agent, repository)?;
+    let (release, asset) = find_release_and_asset(&releases, &download_args.asset)?;
+
+    // fail before downloading, if the asset can not be extracted anyway
+    let archive_format = extract_options
+        .as_ref()
+        .map(|_| {
+            extract::ArchiveFormat::from_file_name(&asset.name).ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Can not extract \"{}\", the archive format is unknown",
+                    asset.name
+                ))
+            })
+        })
+        .transpose()?;
+    if download_args.stream && archive_format == Some(extract::ArchiveFormat::Zip) {
+        return Err(Error::InvalidArgument(format!(
+            "Can not extract \"{}\" while downloading, zip archives have to be downloaded first",
+            asset.name
+        )));
+    }
+
+    let options = get_download_options(&download_args.verify, &agent, repository, release, asset)?;
+
+    // --stream requires --extract, so both are set
+    if let (true, Some(extract_options), Some(archive_format)) =
+        (download_args.stream, &extract_options, archive_format)
+    {
+        return stream_extract_asset(
+            &download_args,
+            &agent,
+            asset,
+            archive_format,
+            extract_options,
+            &options,
+        );
+    }
+
+    let out_path = match &download_args.output_dir {
+        Some(output_dir) => {
+            fs::create_dir_all(output_dir)?;
+            output_dir.join(&asset.name)
+        }
+        None => PathBuf::from(&asset.name),
+    };
+    let out_filename = &out_path.display().to_string();
+    download_verified_asset(
+        &agent,
+        repository,
+        release,
+        asset,
+        &signature_verifiers,
+        &options,
+        &out_path,
+    )?;
+
+    eprintln!(r#"Successfully wrote to file "{}""#, &out_filename);
+
+    if let (Some(extract_options), Some(archive_format)) = (extract_options, archive_format) {
+        let destination = get_extract_destination(&download_args);
+        let extracted = extract::extract_archive(
+            Path::new(out_filename),
+            archive_format,
+            &destination,
+            &extract_options,
+        )?;
+        // the archive is not needed anymore, it is only kept if extraction fails
+        fs::remove_file(out_filename)?;
         eprintln!(
-            r#"Could not find Pattern "{asset_pattern}" in {tag_string} in releases of repository "{repository}""#,
-            asset_pattern = parsed_args.asset_pattern,
-            repository = parsed_args.repository.passed_string,
+            r#"Extracted {} files to "{}""#,
+            extracted.len(),
+            destination.display()
         );
-        process::exit(1);
+        if download_args.print_filename {
+            print!("{}", destination.display())
+        }
+        return Ok(());
+    }
+
+    let out_filename = if download_args.decompress {
+        let decompressed = extract::decompress_file_in_place(Path::new(out_filename))?;
+        let decompressed = decompressed.display().to_string();
+        eprintln!(r#"Decompressed to file "{decompressed}""#);
+        decompressed
+    } else {
+        out_filename.to_string()
     };
+    if download_args.executable {
+        extract::set_executable(Path::new(&out_filename))?;
+    }
 
-    asset
+    if download_args.print_filename {
+        print!(r#"{}"#, &out_filename)
+    }
+    Ok(())
 }
 
-fn make_get_request(
-    agent: &Agent,
-    url: &str,
-    headers: &[String],
-) -> Result<Response, Box<ureq::Error>> {
-    let mut request = agent.get(url).set("user-agent", USERAGENT);
-    for header in headers {
-        // according to the first paragraph of the following mdn site, whitespace before the value
-        // is ignored, so we don't need to remove anything
-        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers
-        let (header_name, value) = header.split_once(":").unwrap_or_else(|| {
-            eprintln!("Http header \"{header}\" has invalid format, must be: \"header-name: header-value\"");
-            process::exit(1);
-        });
-        request = request.set(header_name, value);
+fn get_state_dir(state_args: &arguments::StateArgs) -> Result<PathBuf, Error> {
+    match state_args.state_dir.clone() {
+        Some(state_dir) => Ok(state_dir),
+        None => install::default_state_dir().ok_or_else(|| {
+            Error::InvalidArgument(
+                "Could not determine the home directory, pass --state-dir".to_string(),
+            )
+        }),
     }
+}
 
-    request.call().map_err(Box::new)
+fn get_cache_dir(cache_args: &arguments::CacheArgs) -> Result<PathBuf, Error> {
+    match cache_args.cache_dir.clone() {
+        Some(cache_dir) => Ok(cache_dir),
+        None => install::default_cache_dir().ok_or_else(|| {
+            Error::InvalidArgument(
+                "Could not determine the home directory, pass --cache-dir".to_string(),
+            )
+        }),
+    }
 }
 
-fn get_content_length(response: &Response) -> Option<usize> {
-    response
-        .header("content-length")
-        .map_or_else(|| None, |input| input.parse::<usize>().ok())
+fn install_asset(install_args: arguments::InstallArgs) -> Result<(), Error> {
+    let signature_verifiers = get_signature_verifiers(&install_args.verify)?;
+    let state_dir = get_state_dir(&install_args.state)?;
+    let home_dir_error = |option: &str| {
+        Error::InvalidArgument(format!(
+            "Could not determine the home directory, pass {option}"
+        ))
+    };
+    let bin_dir = match install_args.bin_dir.clone() {
+        Some(bin_dir) => bin_dir,
+        None => install::default_bin_dir().ok_or_else(|| home_dir_error("--bin-dir"))?,
+    };
+    let tools_dir = match install_args.tools_dir.clone() {
+        Some(tools_dir) => tools_dir,
+        None => install::default_tools_dir().ok_or_else(|| home_dir_error("--tools-dir"))?,
+    };
+    // absolute, so the shims and receipts stay valid independent of the working directory
+    let options = install::InstallOptions::new(
+        std::path::absolute(bin_dir)?,
+        std::path::absolute(tools_dir)?,
+        std::path::absolute(get_cache_dir(&install_args.cache)?)?,
+        install_args.name.clone(),
+        install_args.binary.as_deref(),
+    )?;
+
+    let repository = &install_args.asset.repository;
+    let agent: Agent = get_default_agent(repository)?;
+    let releases = get_releases(&agent, repository)?;
+    let (release, asset) = find_release_and_asset(&releases, &install_args.asset)?;
+    install_release_asset(
+        &agent,
+        &install_args.asset,
+        &install_args.verify,
+        &signature_verifiers,
+        release,
+        asset,
+        &options,
+        &state_dir,
+    )
+    .map(|_| ())
 }
 
-fn create_progress_bar(content_length: usize) -> ProgressBar {
-    let pb = ProgressBar::new(content_length as u64);
-    let pb_style = ProgressStyle::with_template(
-        "{spinner:.green} [{elapsed_precise}] [{wide_bar:.green/red}] {bytes}/{total_bytes}",
+// downloads, verifies and installs the asset, then records the receipt
+#[allow(clippy::too_many_arguments)]
+fn install_release_asset(
+    agent: &Agent,
+    asset_args: &arguments::AssetArgs,
+    verify_args: &arguments::VerifyArgs,
+    signature_verifiers: &[verify::SignatureVerifier],
+    release: &Release,
+    asset: &Asset,
+    options: &install::InstallOptions,
+    state_dir: &Path,
+) -> Result<install::Receipt, Error> {
+    let staging = install::StagingDir::new(&options.tools_dir)?;
+    let (downloaded_path, downloaded) = fetch_release_asset(
+        agent,
+        &asset_args.repository,
+        verify_args,
+        signature_verifiers,
+        release,
+        asset,
+        &options.cache_dir,
+        &staging,
+    )?;
+    install_downloaded_asset(
+        asset_args,
+        &release.tag_name,
+        asset,
+        &downloaded_path,
+        &downloaded,
+        &staging,
+        options,
+        state_dir,
     )
-    // this hard coded template will always succeed compiling,
-    // so it's okay to unwrap
-    .unwrap()
-    .progress_chars("=>-");
-    pb.set_style(pb_style);
-    pb
-}
-
-fn create_and_init_progress_bar(content_length_option: Option<usize>) -> Option<ProgressBar> {
-    let content_length = content_length_option?;
-    let pb = create_progress_bar(content_length);
-    pb.set_position(0);
-    Some(pb)
-}
-
-fn stream_response_into_file(
-    response: Response,
-    mut out_file: File,
-    pb_option: &Option<ProgressBar>,
-) {
-    let mut stream = response.into_reader();
-
-    let mut bytes_downloaded = 0;
-    let mut buffer = [0_u8; 8192];
-
-    let mut stderr_locked = stderr().lock();
-
-    loop {
-        let chunk_result = stream.read(&mut buffer);
-        match chunk_result {
-            Err(error) => {
-                // can we even properly handle the potential error
-                // of writeln! ?
-                // If it fails we can't notify the user anyway
-                writeln!(stderr_locked, "Error reading stream:\n{error}").unwrap();
-                process::exit(1);
+}
+
+// returns the verified asset, taken from the cache or downloaded into staging and cached
+#[allow(clippy::too_many_arguments)]
+fn fetch_release_asset(
+    agent: &Agent,
+    repository: &Repository,
+    verify_args: &arguments::VerifyArgs,
+    signature_verifiers: &[verify::SignatureVerifier],
+    release: &Release,
+    asset: &Asset,
+    cache_dir: &Path,
+    staging: &install::StagingDir,
+) -> Result<(PathBuf, DownloadedAsset), Error> {
+    let download_options = get_download_options(verify_args, agent, repository, release, asset)?;
+    let cache_path = install::cache_path(cache_dir, repository, &release.tag_name, &asset.name);
+    let cached = cache_path
+        .is_file()
+        .then(|| verify_local_asset(asset, &cache_path, &download_options));
+    match cached {
+        Some(Ok(downloaded)) => {
+            eprintln!(r#"Using cached "{}""#, cache_path.display());
+            for digest in &downloaded.verified_digests {
+                eprintln!("Verified {} digest", digest.algorithm.name());
             }
-            Ok(read_size) => {
-                // download has finished
-                if read_size == 0 {
-                    break;
-                }
-                let file_write_result = out_file.write(&buffer[0..read_size]);
-                if let Err(error) = file_write_result {
-                    writeln!(stderr_locked, "Could not write to file:\n{error}").unwrap();
-                    process::exit(1);
-                }
+            let signatures =
+                verify::fetch_signatures(agent, repository, release, asset, signature_verifiers)?;
+            for description in verify::verify_signatures(&signatures, &cache_path, &asset.name)? {
+                eprintln!("Verified {description}");
+            }
+            install::touch_cached_asset(&cache_path)?;
+            Ok((cache_path, downloaded))
+        }
+        cached => {
+            if let Some(Err(e)) = cached {
+                eprintln!("Ignoring the cached download: {e}");
+            }
+            let downloaded_path = staging.path().join(&asset.name);
+            let downloaded = download_verified_asset(
+                agent,
+                repository,
+                release,
+                asset,
+                signature_verifiers,
+                &download_options,
+                &downloaded_path,
+            )?;
+            install::cache_asset(&downloaded_path, &cache_path)?;
+            Ok((downloaded_path, downloaded))
+        }
+    }
+}
 
-                bytes_downloaded += read_size;
+// installs the asset recorded in the lockfile, which has to match its sha256 and size
+fn install_locked_asset(
+    agent: &Agent,
+    asset_args: &arguments::AssetArgs,
+    lockfile_path: &Path,
+    locked: &manifest::LockedAsset,
+    options: &install::InstallOptions,
+    state_dir: &Path,
+) -> Result<install::Receipt, Error> {
+    let repository = &asset_args.repository;
+    let asset = locked.to_asset();
+    let locked_digest = locked
+        .sha256_digest()
+        .map_err(|reason| Error::InvalidManifest {
+            path: lockfile_path.to_path_buf(),
+            reason,
+        })?;
+    let download_options = DownloadOptions {
+        show_progress: true,
+        verify_digest: true,
+        expected_digests: vec![locked_digest],
+    };
 
-                if let Some(ref pb) = pb_option {
-                    pb.set_position(bytes_downloaded as u64);
-                }
+    let staging = install::StagingDir::new(&options.tools_dir)?;
+    let cache_path =
+        install::cache_path(&options.cache_dir, repository, &locked.tag, &locked.asset);
+    let cached = cache_path
+        .is_file()
+        .then(|| verify_local_asset(&asset, &cache_path, &download_options));
+    let (downloaded_path, downloaded) = match cached {
+        Some(Ok(downloaded)) => {
+            eprintln!(r#"Using cached "{}""#, cache_path.display());
+            install::touch_cached_asset(&cache_path)?;
+            (cache_path, downloaded)
+        }
+        cached => {
+            if let Some(Err(e)) = cached {
+                eprintln!("Ignoring the cached download: {e}");
             }
+            eprintln!(r#"Downloading "{}""#, locked.url);
+            let downloaded_path = staging.path().join(&locked.asset);
+            let downloaded = download_asset_from_url(
+                agent,
+                repository,
+                &asset,
+                &downloaded_path,
+                &download_options,
+            )?;
+            install::cache_asset(&downloaded_path, &cache_path)?;
+            (downloaded_path, downloaded)
         }
+    };
+    if downloaded.size != locked.size {
+        return Err(Error::Verification {
+            asset: locked.asset.clone(),
+            reason: format!(
+                "size is {} bytes, but {} bytes have been locked",
+                downloaded.size, locked.size
+            ),
+        });
     }
+    for digest in &downloaded.verified_digests {
+        eprintln!("Verified locked {} digest", digest.algorithm.name());
+    }
+    install_downloaded_asset(
+        asset_args,
+        &locked.tag,
+        &asset,
+        &downloaded_path,
+        &downloaded,
+        &staging,
+        options,
+        state_dir,
+    )
 }
 
-fn print_releases(releases_query_args: arguments::ReleasesQueryArgs) {
-    let agent: Agent = get_default_agent(&releases_query_args.repository);
+// installs the executable of the downloaded asset as version "tag" and records the receipt
+#[allow(clippy::too_many_arguments)]
+fn install_downloaded_asset(
+    asset_args: &arguments::AssetArgs,
+    tag: &str,
+    asset: &Asset,
+    downloaded_path: &Path,
+    downloaded: &DownloadedAsset,
+    staging: &install::StagingDir,
+    options: &install::InstallOptions,
+    state_dir: &Path,
+) -> Result<install::Receipt, Error> {
+    let repository = &asset_args.repository;
+    let executable =
+        install::locate_executable(downloaded_path, &asset.name, staging.path(), options)?;
+    // bare executables are usually named after the platform, e.g. "tool-linux-amd64",
+    // so they are installed under the name of the repository
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None if executable.starts_with(staging.path().join("extracted")) => executable
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_else(|| repository.name.clone()),
+        None if asset.name.to_lowercase().ends_with(".exe") => format!("{}.exe", repository.name),
+        None => repository.name.clone(),
+    };
+    let tool_dir = options.tools_dir.join(&name);
+    // the versions installed before are kept, unless the name has been used for
+    // a tool of another repository or they are in another tools directory,
+    // then that tool is removed, so none of its files are left untracked
+    let previous = install::Receipt::load(state_dir, &name)?;
+    let versions = match previous {
+        Some(previous)
+            if previous.repository == repository.canonical_string()
+                && previous.tool_dir.as_ref() == Some(&tool_dir) =>
+        {
+            previous.versions
+        }
+        Some(previous) => {
+            remove_tool(state_dir, &previous, false)?;
+            vec![]
+        }
+        None => vec![],
+    };
+    let installed = install::install_version(&executable, &tool_dir, tag, &name)?;
 
-    let repository: arguments::Repository = releases_query_args.repository;
-    let releases = get_releases(&agent, &repository);
-    let releases_iter = releases
+    let version = install::InstalledVersion {
+        tag: tag.to_string(),
+        asset: asset.name.clone(),
+        url: asset.browser_download_url.clone(),
+        sha256: downloaded.sha256.to_hex(),
+        executable_sha256: Some(install::file_sha256(&installed)?),
+        installed_at: install::format_timestamp(SystemTime::now()),
+    };
+    let mut receipt = install::Receipt {
+        name,
+        repository: repository.canonical_string(),
+        website: repository.website.clone(),
+        asset_pattern: asset_args.asset_pattern.clone(),
+        allow_prerelease: asset_args.allow_prerelease,
+        tag_pattern: asset_args.tag_pattern.clone(),
+        version_requirement: asset_args.version_requirement.clone(),
+        tag_prefix: asset_args.tag_prefix.clone(),
+        binary: options
+            .binary
+            .as_ref()
+            .map(|binary| binary.glob().glob().to_string()),
+        version: version.clone(),
+        files: vec![],
+        tool_dir: Some(tool_dir),
+        pinned: false,
+        versions,
+    };
+    install::add_version(&mut receipt, version);
+    install::switch_version(&mut receipt, tag, &options.bin_dir)?;
+    receipt.save(state_dir)?;
+    eprintln!(
+        r#"Installed "{}" from release "{tag}""#,
+        receipt.files[0].display()
+    );
+    Ok(receipt)
+}
+
+fn update_installed(
+    update_args: arguments::UpdateArgs,
+    config: &config::Config,
+) -> Result<(), Error> {
+    let signature_verifiers = get_signature_verifiers(&update_args.verify)?;
+    let state_dir = get_state_dir(&update_args.state)?;
+    let receipts = if update_args.names.is_empty() {
+        install::load_receipts(&state_dir)?
+    } else {
+        update_args
+            .names
+            .iter()
+            .map(|name| load_receipt(&state_dir, name))
+            .collect::<Result<_, _>>()?
+    };
+
+    // a failing update does not keep the other tools from being updated,
+    // the first error is returned once all have been tried
+    let mut first_error = None;
+    for receipt in receipts {
+        if let Err(e) = update_tool(
+            &update_args,
+            config,
+            &signature_verifiers,
+            &receipt,
+            &state_dir,
+        ) {
+            eprintln!(r#"Updating "{}" failed: {e}"#, receipt.name);
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+fn update_tool(
+    update_args: &arguments::UpdateArgs,
+    config: &config::Config,
+    signature_verifiers: &[verify::SignatureVerifier],
+    receipt: &install::Receipt,
+    state_dir: &Path,
+) -> Result<(), Error> {
+    let repository = config.resolve_repository(
+        receipt.repository.clone(),
+        Some(receipt.website.clone()),
+        None,
+        vec![],
+    )?;
+    let asset_args = arguments::AssetArgs {
+        repository,
+        asset_pattern: receipt.asset_pattern.clone(),
+        tag: None,
+        tag_pattern: receipt.tag_pattern.clone(),
+        version_requirement: receipt.version_requirement.clone(),
+        tag_prefix: receipt.tag_prefix.clone(),
+        allow_prerelease: receipt.allow_prerelease,
+    };
+    let agent: Agent = get_default_agent(&asset_args.repository)?;
+    let releases = get_releases(&agent, &asset_args.repository)?;
+    let (release, asset) = find_release_and_asset(&releases, &asset_args)?;
+    if release.tag_name == receipt.version.tag {
+        eprintln!(
+            r#""{}" is up to date ({})"#,
+            receipt.name, receipt.version.tag
+        );
+        return Ok(());
+    }
+    // pinned tools are only updated when they are named explicitly
+    if receipt.pinned && update_args.names.is_empty() {
+        eprintln!(
+            r#""{}" is pinned to {} ({} is available)"#,
+            receipt.name, receipt.version.tag, release.tag_name
+        );
+        return Ok(());
+    }
+    // stdout only lists the outdated tools, so it can be used by scripts
+    println!(
+        "{}\t{}\t{}",
+        receipt.name, receipt.version.tag, release.tag_name
+    );
+    if update_args.dry_run {
+        return Ok(());
+    }
+
+    let bin_dir = get_bin_dir(receipt)?;
+    // the release is still installed, e.g. after a rollback
+    if receipt
+        .versions
         .iter()
-        .filter(|release| !release.prerelease || releases_query_args.allow_prerelease)
-        .take(releases_query_args.count.into());
-    for release in releases_iter {
-        println!("{}", release.tag_name);
+        .any(|version| version.tag == release.tag_name)
+    {
+        let mut receipt = receipt.clone();
+        install::switch_version(&mut receipt, &release.tag_name, &bin_dir)?;
+        receipt.pinned = false;
+        receipt.save(state_dir)?;
+        eprintln!(
+            r#"Switched "{}" to release "{}""#,
+            receipt.name, release.tag_name
+        );
+        return Ok(());
     }
+
+    let tools_dir = match receipt.tool_dir.as_deref().and_then(Path::parent) {
+        Some(tools_dir) => tools_dir.to_path_buf(),
+        None => install::default_tools_dir().ok_or_else(|| {
+            Error::InvalidArgument("Could not determine the home directory".to_string())
+        })?,
+    };
+    let options = install::InstallOptions::new(
+        bin_dir,
+        tools_dir,
+        std::path::absolute(get_cache_dir(&update_args.cache)?)?,
+        Some(receipt.name.clone()),
+        receipt.binary.as_deref(),
+    )?;
+    install_release_asset(
+        &agent,
+        &asset_args,
+        &update_args.verify,
+        signature_verifiers,
+        release,
+        asset,
+        &options,
+        state_dir,
+    )
+    .map(|_| ())
+}
+
+// the shim (or executable of tools installed without versions) is the first file
+fn get_bin_dir(receipt: &install::Receipt) -> Result<PathBuf, Error> {
+    receipt
+        .files
+        .first()
+        .and_then(|file| file.parent())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            Error::Install(format!(
+                "the receipt of \"{}\" does not contain the installed files",
+                receipt.name
+            ))
+        })
 }
 
-fn print_assets(assets_query_args: arguments::AssetsQueryArgs) {
-    let agent: Agent = get_default_agent(&assets_query_args.repository);
+fn load_receipt(state_dir: &Path, name: &str) -> Result<install::Receipt, Error> {
+    install::Receipt::load(state_dir, name)?.ok_or_else(|| {
+        Error::InvalidArgument(format!("\"{name}\" has not been installed with grd"))
+    })
+}
 
-    let releases = get_releases(&agent, &assets_query_args.repository);
-    // if no tag is specified, prereleases are not allowed
-    // however if a tag is specified, the user explictly chose
-    // a tag that might be a prerelease, so in this case it
-    // will be allowed
-    let allow_prerelease = assets_query_args.tag.is_some();
-    let Some(release) = find_release(
-        &releases,
-        assets_query_args.tag.as_deref(),
-        allow_prerelease,
-    ) else {
-        match &assets_query_args.tag {
-            Some(tag) => eprintln!("Could not find release with tag \"{tag}\""),
-            None => eprintln!("Could not find latest tag"),
+fn use_version(state_dir: &Path, mut receipt: install::Receipt, tag: &str) -> Result<(), Error> {
+    let bin_dir = get_bin_dir(&receipt)?;
+    install::switch_version(&mut receipt, tag, &bin_dir)?;
+    receipt.save(state_dir)?;
+    eprintln!(r#"Switched "{}" to release "{tag}""#, receipt.name);
+    Ok(())
+}
+
+fn uninstall_tool(uninstall_args: arguments::UninstallArgs) -> Result<(), Error> {
+    let state_dir = get_state_dir(&uninstall_args.state)?;
+    let receipt = load_receipt(&state_dir, &uninstall_args.name)?;
+    remove_tool(&state_dir, &receipt, uninstall_args.force)
+}
+
+fn remove_tool(state_dir: &Path, receipt: &install::Receipt, force: bool) -> Result<(), Error> {
+    for removed in install::uninstall(receipt, force)? {
+        eprintln!(r#"Removed "{}""#, removed.display());
+    }
+    receipt.remove(state_dir)?;
+    eprintln!(r#"Uninstalled "{}""#, receipt.name);
+    Ok(())
+}
+
+fn collect_garbage(gc_args: arguments::GcArgs) -> Result<(), Error> {
+    let mut removed = vec![];
+    if let Some(keep) = gc_args.keep {
+        let state_dir = get_state_dir(&gc_args.state)?;
+        for mut receipt in install::load_receipts(&state_dir)? {
+            removed.extend(install::remove_old_versions(
+                &mut receipt,
+                keep,
+                gc_args.dry_run,
+                &state_dir,
+            )?);
         }
-        process::exit(1);
+    }
+    if gc_args.cache_max_age.is_some() || gc_args.cache_max_size.is_some() {
+        let policy = install::CachePolicy {
+            max_age: gc_args.cache_max_age,
+            max_size: gc_args.cache_max_size,
+        };
+        removed.extend(install::clean_cache(
+            &get_cache_dir(&gc_args.cache)?,
+            &policy,
+            SystemTime::now(),
+            gc_args.dry_run,
+        )?);
+    }
+
+    let verb = if gc_args.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
     };
-    let regex = get_compiled_asset_pattern_or_exit(&assets_query_args.pattern);
-    let assets = find_assets_in_release(release, &regex);
-    for asset in assets {
-        println!("{}", asset.name);
+    for removed in &removed {
+        eprintln!(
+            r#"{verb} "{}" ({})"#,
+            removed.path.display(),
+            install::format_size(removed.size)
+        );
     }
+    let freed: u64 = removed.iter().map(|removed| removed.size).sum();
+    eprintln!(
+        "{} {}",
+        if gc_args.dry_run {
+            "Would free"
+        } else {
+            "Freed"
+        },
+        install::format_size(freed)
+    );
+    Ok(())
 }
 
-fn get_github_asset_api_url(owner: &str, repository: &str, asset_id: i64) -> String {
-    format!("https://api.github.com/repos/{owner}/{repository}/releases/assets/{asset_id}")
+fn rollback_tool(rollback_args: arguments::RollbackArgs) -> Result<(), Error> {
+    let state_dir = get_state_dir(&rollback_args.state)?;
+    let receipt = load_receipt(&state_dir, &rollback_args.name)?;
+    let previous = install::previous_version(&receipt)
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "\"{}\" has no version installed before {}",
+                receipt.name, receipt.version.tag
+            ))
+        })?
+        .tag
+        .clone();
+    use_version(&state_dir, receipt, &previous)
 }
 
-fn download_assets(mut download_args: arguments::DownloadArgs) {
-    let compiled_asset_pattern = get_compiled_asset_pattern_or_exit(&download_args.asset_pattern);
+fn use_tool_version(use_args: arguments::UseArgs) -> Result<(), Error> {
+    let state_dir = get_state_dir(&use_args.state)?;
+    let mut receipt = load_receipt(&state_dir, &use_args.name)?;
+    // the chosen version is kept by "update" from then on
+    receipt.pinned = true;
+    use_version(&state_dir, receipt, &use_args.tag)
+}
 
-    let repository = &download_args.repository;
-    let agent: Agent = get_default_agent(repository);
-    let releases = get_releases(&agent, repository);
-    let asset = get_asset_or_exit(&releases, &download_args, &compiled_asset_pattern);
+// installs, switches and removes tools until they match the manifest
+fn sync_manifest(sync_args: arguments::SyncArgs, config: &config::Config) -> Result<(), Error> {
+    let manifest = manifest::Manifest::load(&sync_args.manifest)?;
+    let state_dir = get_state_dir(&sync_args.state)?;
+    let home_dir_error = |option: &str| {
+        Error::InvalidArgument(format!(
+            "Could not determine the home directory, pass {option}"
+        ))
+    };
+    let bin_dir = match sync_args.bin_dir.clone().or(manifest.settings.bin_dir) {
+        Some(bin_dir) => bin_dir,
+        None => install::default_bin_dir().ok_or_else(|| home_dir_error("--bin-dir"))?,
+    };
+    let tools_dir = match sync_args.tools_dir.clone().or(manifest.settings.tools_dir) {
+        Some(tools_dir) => tools_dir,
+        None => install::default_tools_dir().ok_or_else(|| home_dir_error("--tools-dir"))?,
+    };
+    let (bin_dir, tools_dir, cache_dir) = (
+        std::path::absolute(bin_dir)?,
+        std::path::absolute(tools_dir)?,
+        std::path::absolute(get_cache_dir(&sync_args.cache)?)?,
+    );
+    let lockfile = if sync_args.locked {
+        let lockfile_path = manifest::lockfile_path(&sync_args.manifest);
+        let lockfile =
+            manifest::Lockfile::load(&lockfile_path)?.ok_or_else(|| Error::InvalidManifest {
+                path: lockfile_path,
+                reason: "the lockfile does not exist, create it with \"grd lock\"".to_string(),
+            })?;
+        Some(lockfile)
+    } else {
+        None
+    };
 
-    // drop immutable reference and get a mutable reference
-    let repository = &mut download_args.repository;
+    // like update, a failing tool does not keep the others from being synced
+    let mut first_error = None;
+    for (name, tool) in &manifest.tools {
+        let result = install::InstallOptions::new(
+            bin_dir.clone(),
+            tools_dir.clone(),
+            cache_dir.clone(),
+            Some(name.clone()),
+            tool.binary.as_deref(),
+        )
+        .and_then(|options| {
+            sync_tool(
+                &sync_args,
+                config,
+                name,
+                tool,
+                lockfile.as_ref(),
+                &options,
+                &state_dir,
+            )
+        });
+        if let Err(e) = result {
+            eprintln!(r#"Syncing "{name}" failed: {e}"#);
+            first_error.get_or_insert(e);
+        }
+    }
 
-    // printing to stderr, since posix (or unix?)
-    // says progress is written to stderr
-    // this makes sense especially if we pipe the name
-    // into a script: the script gets the downloaded
-    // file name and the user can still see the progress
-    eprintln!(r#"Downloading "{}""#, &asset.name);
+    // tools installed into other directories are not managed by the manifest
+    let is_managed = |receipt: &install::Receipt| {
+        receipt
+            .tool_dir
+            .as_ref()
+            .is_some_and(|tool_dir| tool_dir.starts_with(&tools_dir))
+            || receipt
+                .files
+                .first()
+                .is_some_and(|shim| shim.parent() == Some(bin_dir.as_path()))
+    };
+    for receipt in install::load_receipts(&state_dir)? {
+        if manifest.tools.contains_key(&receipt.name) || !is_managed(&receipt) {
+            continue;
+        }
+        println!("{}\t{}\t-", receipt.name, receipt.version.tag);
+        if sync_args.dry_run {
+            continue;
+        }
+        if let Err(e) = remove_tool(&state_dir, &receipt, sync_args.force) {
+            eprintln!(r#"Removing "{}" failed: {e}"#, receipt.name);
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
 
-    let url_buffer: String;
-    let url = if matches!(repository.website, GitWebsite::GitHub) {
-        repository
-            .headers
-            .push("Accept: application/octet-stream".to_string());
-        url_buffer = get_github_asset_api_url(&repository.owner, &repository.name, asset.id);
-        url_buffer.as_str()
-    } else {
-        &asset.browser_download_url
+// what a tool is synced to, the release resolved from the manifest or the locked asset
+enum SyncTarget<'a> {
+    Release(&'a Release, &'a Asset),
+    Locked(&'a manifest::LockedAsset),
+}
+
+impl SyncTarget<'_> {
+    fn tag(&self) -> &str {
+        match self {
+            SyncTarget::Release(release, _) => &release.tag_name,
+            SyncTarget::Locked(locked) => &locked.tag,
+        }
+    }
+
+    fn is(&self, version: &install::InstalledVersion) -> bool {
+        match self {
+            SyncTarget::Release(release, asset) => {
+                version.tag == release.tag_name && version.asset == asset.name
+            }
+            SyncTarget::Locked(locked) => {
+                version.tag == locked.tag
+                    && version.asset == locked.asset
+                    && version.sha256 == locked.sha256
+            }
+        }
+    }
+}
+
+// the locked asset of the tool on this platform, if the lockfile matches the manifest
+fn get_locked_asset<'l>(
+    lockfile: &'l manifest::Lockfile,
+    name: &str,
+    asset_args: &arguments::AssetArgs,
+) -> Result<&'l manifest::LockedAsset, Error> {
+    let platform = manifest::current_platform();
+    let locked = lockfile.get(name, &platform).ok_or_else(|| {
+        Error::InvalidArgument(format!(
+            "\"{name}\" is not locked for \"{platform}\", update the lockfile with \"grd lock\""
+        ))
+    })?;
+    let tag_pattern = asset_args
+        .tag_pattern
+        .as_deref()
+        .map(Regex::new)
+        .transpose()?;
+    let requirement = asset_args
+        .version_requirement
+        .as_deref()
+        .map(version::parse_requirement)
+        .transpose()?;
+    let outdated = locked.repository != asset_args.repository.canonical_string()
+        || asset_args
+            .tag
+            .as_ref()
+            .is_some_and(|tag| *tag != locked.tag)
+        || tag_pattern
+            .as_ref()
+            .is_some_and(|tag_pattern| !tag_pattern.is_match(&locked.tag))
+        || requirement.is_some_and(|requirement| {
+            version::matching_version(
+                &locked.tag,
+                &requirement,
+                tag_pattern.as_ref(),
+                asset_args.tag_prefix.as_deref(),
+                asset_args.allow_prerelease,
+            )
+            .is_none()
+        });
+    if outdated {
+        return Err(Error::InvalidArgument(format!(
+            "the lockfile does not match the manifest for \"{name}\", update it with \"grd lock\""
+        )));
+    }
+    Ok(locked)
+}
+
+fn sync_tool(
+    sync_args: &arguments::SyncArgs,
+    config: &config::Config,
+    name: &str,
+    tool: &manifest::Tool,
+    lockfile: Option<&manifest::Lockfile>,
+    options: &install::InstallOptions,
+    state_dir: &Path,
+) -> Result<(), Error> {
+    let asset_args = tool.asset_args(name, config)?;
+    let agent: Agent = get_default_agent(&asset_args.repository)?;
+    // with a lockfile nothing is resolved again
+    let releases = match lockfile {
+        Some(_) => vec![],
+        None => get_releases(&agent, &asset_args.repository)?,
+    };
+    let target = match lockfile {
+        Some(lockfile) => SyncTarget::Locked(get_locked_asset(lockfile, name, &asset_args)?),
+        None => {
+            let (release, asset) = find_release_and_asset(&releases, &asset_args)?;
+            SyncTarget::Release(release, asset)
+        }
     };
 
-    let response = make_get_request(&agent, url, &repository.headers).unwrap_or_else(|e| {
-        eprintln!("Error downloading file:\n{e}");
-        process::exit(1);
+    let tool_dir = options.tools_dir.join(name);
+    let binary = options
+        .binary
+        .as_ref()
+        .map(|binary| binary.glob().glob().to_string());
+    let previous = install::Receipt::load(state_dir, name)?;
+    // versions can only be kept if the tool is installed from the same
+    // repository into the same directories
+    let reusable = previous.as_ref().filter(|receipt| {
+        receipt.repository == asset_args.repository.canonical_string()
+            && receipt.tool_dir.as_ref() == Some(&tool_dir)
+            && get_bin_dir(receipt).is_ok_and(|bin_dir| bin_dir == options.bin_dir)
     });
+    let has_version = |receipt: &install::Receipt, version: &install::InstalledVersion| {
+        receipt.binary == binary && target.is(version)
+    };
+    // a tag in the manifest keeps update from moving the tool
+    let pinned = tool.tag.is_some();
 
-    let out_filename = &asset.name;
+    if let Some(receipt) = reusable.filter(|receipt| has_version(receipt, &receipt.version)) {
+        eprintln!(r#""{name}" is up to date ({})"#, receipt.version.tag);
+        if !sync_args.dry_run
+            && (receipt.asset_pattern != asset_args.asset_pattern
+                || receipt.allow_prerelease != asset_args.allow_prerelease
+                || receipt.tag_pattern != asset_args.tag_pattern
+                || receipt.version_requirement != asset_args.version_requirement
+                || receipt.tag_prefix != asset_args.tag_prefix
+                || receipt.pinned != pinned)
+        {
+            let mut receipt = receipt.clone();
+            receipt.asset_pattern = asset_args.asset_pattern.clone();
+            receipt.allow_prerelease = asset_args.allow_prerelease;
+            receipt.tag_pattern = asset_args.tag_pattern.clone();
+            receipt.version_requirement = asset_args.version_requirement.clone();
+            receipt.tag_prefix = asset_args.tag_prefix.clone();
+            receipt.pinned = pinned;
+            receipt.save(state_dir)?;
+        }
+        return Ok(());
+    }
+    let installed_tag = previous
+        .as_ref()
+        .map_or("-", |receipt| receipt.version.tag.as_str());
+    println!("{name}\t{installed_tag}\t{}", target.tag());
+    if sync_args.dry_run {
+        return Ok(());
+    }
 
-    let out_file = File::create(out_filename).unwrap_or_else(|e| {
-        eprintln!("Error creating file:\n{e}");
-        process::exit(1);
-    });
+    let mut receipt = match (reusable, &previous) {
+        // the release is still installed, e.g. after going back to an older tag
+        (Some(receipt), _)
+            if receipt
+                .versions
+                .iter()
+                .any(|version| has_version(receipt, version)) =>
+        {
+            let mut receipt = receipt.clone();
+            install::switch_version(&mut receipt, target.tag(), &options.bin_dir)?;
+            eprintln!(r#"Switched "{name}" to release "{}""#, target.tag());
+            receipt
+        }
+        (reusable, previous) => {
+            // installed somewhere else, it is moved by installing it again
+            if let (None, Some(previous)) = (reusable, previous) {
+                remove_tool(state_dir, previous, sync_args.force)?;
+            }
+            match target {
+                SyncTarget::Release(release, asset) => {
+                    let verify_args = tool.verify_args();
+                    let signature_verifiers = get_signature_verifiers(&verify_args)?;
+                    install_release_asset(
+                        &agent,
+                        &asset_args,
+                        &verify_args,
+                        &signature_verifiers,
+                        release,
+                        asset,
+                        options,
+                        state_dir,
+                    )?
+                }
+                SyncTarget::Locked(locked) => install_locked_asset(
+                    &agent,
+                    &asset_args,
+                    &manifest::lockfile_path(&sync_args.manifest),
+                    locked,
+                    options,
+                    state_dir,
+                )?,
+            }
+        }
+    };
+    receipt.asset_pattern = asset_args.asset_pattern.clone();
+    receipt.allow_prerelease = asset_args.allow_prerelease;
+    receipt.tag_pattern = asset_args.tag_pattern.clone();
+    receipt.version_requirement = asset_args.version_requirement.clone();
+    receipt.tag_prefix = asset_args.tag_prefix.clone();
+    receipt.pinned = pinned;
+    receipt.save(state_dir)?;
+    Ok(())
+}
 
-    eprintln!("Writing to file \"{}\"", &out_filename);
+// resolves every tool of the manifest on this platform and writes the lockfile
+fn lock_manifest(lock_args: arguments::LockArgs, config: &config::Config) -> Result<(), Error> {
+    let manifest = manifest::Manifest::load(&lock_args.manifest)?;
+    let lockfile_path = manifest::lockfile_path(&lock_args.manifest);
+    let previous = manifest::Lockfile::load(&lockfile_path)?.unwrap_or_default();
+    let cache_dir = std::path::absolute(get_cache_dir(&lock_args.cache)?)?;
+    let platform = manifest::current_platform();
 
-    let content_length_option = get_content_length(&response);
-    let pb_option = create_and_init_progress_bar(content_length_option);
+    // the assets locked on other platforms are kept
+    let mut lockfile = previous.clone();
+    lockfile
+        .tools
+        .retain(|name, _| manifest.tools.contains_key(name));
+    let staging = install::StagingDir::new(&cache_dir)?;
+    let mut first_error = None;
+    for (name, tool) in &manifest.tools {
+        match lock_tool(name, tool, config, &cache_dir, &staging) {
+            Ok(locked) => {
+                let previous = previous.get(name, &platform);
+                if previous != Some(&locked) {
+                    let previous_tag = previous.map_or("-", |previous| previous.tag.as_str());
+                    println!("{name}\t{previous_tag}\t{}", locked.tag);
+                }
+                lockfile.insert(name, &platform, locked);
+            }
+            Err(e) => {
+                eprintln!(r#"Locking "{name}" failed: {e}"#);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    // a lockfile missing some of the tools is not written
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+    lockfile.save(&lockfile_path)?;
+    eprintln!(r#"Wrote "{}""#, lockfile_path.display());
+    Ok(())
+}
 
-    stream_response_into_file(response, out_file, &pb_option);
+// downloads and verifies the asset of the tool to record its size and sha256
+fn lock_tool(
+    name: &str,
+    tool: &manifest::Tool,
+    config: &config::Config,
+    cache_dir: &Path,
+    staging: &install::StagingDir,
+) -> Result<manifest::LockedAsset, Error> {
+    let asset_args = tool.asset_args(name, config)?;
+    let verify_args = tool.verify_args();
+    let signature_verifiers = get_signature_verifiers(&verify_args)?;
+    let repository = &asset_args.repository;
+    let agent: Agent = get_default_agent(repository)?;
+    let releases = get_releases(&agent, repository)?;
+    let (release, asset) = find_release_and_asset(&releases, &asset_args)?;
+    let (_, downloaded) = fetch_release_asset(
+        &agent,
+        repository,
+        &verify_args,
+        &signature_verifiers,
+        release,
+        asset,
+        cache_dir,
+        staging,
+    )?;
+    Ok(manifest::LockedAsset {
+        repository: repository.canonical_string(),
+        tag: release.tag_name.clone(),
+        asset: asset.name.clone(),
+        url: asset.browser_download_url.clone(),
+        size: downloaded.size,
+        sha256: downloaded.sha256.to_hex(),
+    })
+}
 
-    if let Some(ref pb) = pb_option {
-        pb.finish();
-        eprintln!();
+fn list_installed(list_args: arguments::ListArgs) -> Result<(), Error> {
+    let receipts = install::load_receipts(&get_state_dir(&list_args.state)?)?;
+    if list_args.json {
+        let json = serde_json::to_string_pretty(&receipts)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        println!("{json}");
+        return Ok(());
+    }
+    if receipts.is_empty() {
+        eprintln!("No tools installed");
+        return Ok(());
     }
 
-    eprintln!(r#"Successfully wrote to file "{}""#, &out_filename);
-    if download_args.print_filename {
-        print!(r#"{}"#, &out_filename)
+    let header = ["NAME", "TAG", "REPOSITORY", "ASSET", "INSTALLED"].map(String::from);
+    let rows: Vec<[String; 5]> = receipts
+        .iter()
+        .map(|receipt| {
+            let tag = if receipt.pinned {
+                format!("{} (pinned)", receipt.version.tag)
+            } else {
+                receipt.version.tag.clone()
+            };
+            [
+                receipt.name.clone(),
+                tag,
+                receipt.repository.clone(),
+                receipt.version.asset.clone(),
+                receipt.version.installed_at.clone(),
+            ]
+        })
+        .collect();
+    let mut widths = header.each_ref().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    Ok(())
+}
+
+// the repository arguments are parsed without the configuration,
+// the settings of the host of the repository are added afterwards
+fn apply_host_settings(
+    command_mode: &mut arguments::CommandMode,
+    config: &config::Config,
+) -> Result<(), Error> {
+    let repository = match command_mode {
+        arguments::CommandMode::Query(query_args) => match &mut query_args.query_type {
+            arguments::QueryType::Releases(releases_query_args) => {
+                &mut releases_query_args.repository
+            }
+            arguments::QueryType::Assets(assets_query_args) => &mut assets_query_args.repository,
+        },
+        arguments::CommandMode::Download(download_args) => &mut download_args.asset.repository,
+        arguments::CommandMode::Install(install_args) => &mut install_args.asset.repository,
+        _ => return Ok(()),
+    };
+    *repository = config.apply_host_settings(repository.clone())?;
+    Ok(())
+}
+
+// an explicitly passed configuration has to exist, the default one is optional
+fn load_config() -> Result<config::Config, Error> {
+    if let Some(path) = config::config_path_from_args(std::env::args_os()) {
+        return config::Config::load(&path)?.ok_or_else(|| Error::InvalidConfig {
+            path,
+            reason: "the file does not exist".to_string(),
+        });
+    }
+    match config::default_config_path() {
+        Some(path) => Ok(config::Config::load(&path)?.unwrap_or_default()),
+        None => Ok(config::Config::default()),
     }
 }
 
 fn main() {
-    let args = arguments::Arguments::parse();
+    // a broken configuration must not keep --help and --version from working,
+    // its error is only reported once a subcommand is run
+    let (config, config_error) = match load_config() {
+        Ok(config) => (config, None),
+        Err(e) => (config::Config::default(), Some(e)),
+    };
+    let exit_on_config_error = || {
+        if let Some(e) = &config_error {
+            eprintln!("{e}");
+            exit(e.exit_code());
+        }
+    };
+    // the defaults of the configuration and the alias and host of the repository
+    // are needed to parse the arguments
+    let args_os: Vec<_> = std::env::args_os().collect();
+    let command = arguments::ignore_conflicting_env(
+        config.apply_defaults(arguments::Arguments::command()),
+        &args_os,
+    );
+    let command = config.apply_repository(command, &args_os);
+    let matches = command.try_get_matches_from(args_os).unwrap_or_else(|e| {
+        // arguments may be missing because the configuration could not provide them
+        if !matches!(
+            e.kind(),
+            clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion
+        ) {
+            exit_on_config_error();
+        }
+        e.exit()
+    });
+    exit_on_config_error();
+    let mut args = arguments::Arguments::from_arg_matches(&matches)
+        .unwrap_or_else(|e| e.format(&mut arguments::Arguments::command()).exit());
+
+    if let Err(e) = apply_host_settings(&mut args.command_mode, &config) {
+        eprintln!("{e}");
+        exit(e.exit_code());
+    }
 
-    match args.command_mode {
+    let result = match args.command_mode {
         arguments::CommandMode::Query(query_args) => match query_args.query_type {
             arguments::QueryType::Releases(releases_query_args) => {
-                print_releases(releases_query_args);
-                exit(0);
-            }
-            arguments::QueryType::Assets(assets_query_args) => {
-                print_assets(assets_query_args);
-                exit(0);
+                print_releases(releases_query_args)
             }
+            arguments::QueryType::Assets(assets_query_args) => print_assets(assets_query_args),
         },
-        arguments::CommandMode::Download(download_args) => {
-            download_assets(download_args);
-            exit(0);
-        }
+        arguments::CommandMode::Download(download_args) => download_assets(*download_args),
+        arguments::CommandMode::Install(install_args) => install_asset(*install_args),
+        arguments::CommandMode::List(list_args) => list_installed(list_args),
+        arguments::CommandMode::Update(update_args) => update_installed(*update_args, &config),
+        arguments::CommandMode::Uninstall(uninstall_args) => uninstall_tool(uninstall_args),
+        arguments::CommandMode::Gc(gc_args) => collect_garbage(gc_args),
+        arguments::CommandMode::Rollback(rollback_args) => rollback_tool(rollback_args),
+        arguments::CommandMode::Use(use_args) => use_tool_version(use_args),
+        arguments::CommandMode::Sync(sync_args) => sync_manifest(*sync_args, &config),
+        arguments::CommandMode::Lock(lock_args) => lock_manifest(lock_args, &config),
     };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        exit(e.exit_code());
+    }
 }
@@ -0,0 +1,222 @@
+//! The lockfile (`grd.lock`, next to the manifest) records the release and
+//! asset every tool of the manifest has been resolved to, so `grd sync
+//! --locked` installs exactly the same files again.
+//!
+//! ```toml
+//! [tools.rg.linux-x86_64]
+//! repository = "github.com/BurntSushi/ripgrep"
+//! tag = "14.1.0"
+//! asset = "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+//! url = "https://github.com/BurntSushi/ripgrep/releases/download/14.1.0/ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+//! size = 2566310
+//! sha256 = "…"
+//! ```
+//!
+//! Assets are locked per platform, locking on another platform adds its entries.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{models::Asset, verify::Digest, Error};
+
+const HEADER: &str = "# Generated by `grd lock`, do not edit.\n\n";
+
+/// Path of the lockfile of the manifest at `manifest_path`, e.g. "grd.lock" for "grd.toml".
+pub fn lockfile_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_extension("lock")
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Lockfile {
+    /// The locked assets of every tool, keyed by platform.
+    #[serde(default)]
+    pub tools: BTreeMap<String, BTreeMap<String, LockedAsset>>,
+}
+
+/// The asset a tool has been resolved to on a platform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LockedAsset {
+    /// The repository in the form [`crate::Repository::canonical_string`] returns.
+    pub repository: String,
+    pub tag: String,
+    pub asset: String,
+    pub url: String,
+    pub size: u64,
+    /// Hex encoded sha256 digest of the asset.
+    pub sha256: String,
+}
+
+impl Lockfile {
+    /// Reads the lockfile at `path`, `None` if it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let error = |reason: String| Error::InvalidManifest {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let lockfile: Self = toml::from_str(&content).map_err(|e| error(e.to_string()))?;
+        // a broken hash would otherwise only be noticed when the tool is synced
+        for (name, assets) in &lockfile.tools {
+            for (platform, asset) in assets {
+                asset
+                    .sha256_digest()
+                    .map_err(|reason| error(format!("tool \"{name}\" on {platform}: {reason}")))?;
+            }
+        }
+        Ok(Some(lockfile))
+    }
+
+    /// Writes the lockfile, replacing the previous one atomically.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("lock.tmp");
+        fs::write(&temporary, format!("{HEADER}{content}"))?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str, platform: &str) -> Option<&LockedAsset> {
+        self.tools.get(name)?.get(platform)
+    }
+
+    pub fn insert(&mut self, name: &str, platform: &str, asset: LockedAsset) {
+        self.tools
+            .entry(name.to_string())
+            .or_default()
+            .insert(platform.to_string(), asset);
+    }
+}
+
+impl LockedAsset {
+    /// The asset to download from the locked url.
+    ///
+    /// It has no digest, the locked one from [`Self::sha256_digest`] has to be
+    /// passed as an expected digest, so an invalid hash is never just ignored.
+    pub fn to_asset(&self) -> Asset {
+        Asset {
+            browser_download_url: self.url.clone(),
+            name: self.asset.clone(),
+            // only used by the API of GitHub, locked assets are downloaded from their url
+            id: 0,
+            size: Some(self.size),
+            content_type: None,
+            download_count: None,
+            created_at: None,
+            updated_at: None,
+            digest: None,
+        }
+    }
+
+    /// The locked sha256 digest, an error if it is not 64 hex characters.
+    pub fn sha256_digest(&self) -> Result<Digest, String> {
+        if self.sha256.len() != 64 || !self.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!(
+                "locked sha256 \"{}\" of \"{}\" is not 64 hex characters",
+                self.sha256, self.asset
+            ));
+        }
+        format!("sha256:{}", self.sha256).parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::temp_dir, verify::DigestAlgorithm};
+
+    fn locked_asset(tag: &str) -> LockedAsset {
+        LockedAsset {
+            repository: "github.com/owner/tool".to_string(),
+            tag: tag.to_string(),
+            asset: "tool-linux.tar.gz".to_string(),
+            url: format!("https://github.com/owner/tool/releases/download/{tag}/tool-linux.tar.gz"),
+            size: 1024,
+            sha256: "ab".repeat(32),
+        }
+    }
+
+    #[test]
+    fn test_lockfile_path() {
+        assert_eq!(
+            lockfile_path(Path::new("dotfiles/grd.toml")),
+            Path::new("dotfiles/grd.lock")
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_lockfile() {
+        let dir = temp_dir("lock", "save");
+        let path = dir.join("grd.lock");
+        assert_eq!(Lockfile::load(&path).unwrap(), None);
+
+        let mut lockfile = Lockfile::default();
+        lockfile.insert("tool", "linux-x86_64", locked_asset("v1"));
+        lockfile.insert("tool", "macos-aarch64", locked_asset("v1"));
+        lockfile.insert("tool", "linux-x86_64", locked_asset("v2"));
+        lockfile.save(&path).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().starts_with(HEADER));
+
+        let loaded = Lockfile::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, lockfile);
+        assert_eq!(
+            loaded.get("tool", "linux-x86_64"),
+            Some(&locked_asset("v2"))
+        );
+        assert_eq!(loaded.get("tool", "windows-x86_64"), None);
+
+        fs::write(&path, "[tools.tool.linux-x86_64]\ntag = 1").unwrap();
+        assert!(matches!(
+            Lockfile::load(&path),
+            Err(Error::InvalidManifest { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_locked_asset_digest() {
+        let mut locked = locked_asset("v1");
+        let digest = locked.sha256_digest().unwrap();
+        assert_eq!(digest.algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(digest.value, vec![0xab; 32]);
+        assert_eq!(locked.to_asset().digest, None);
+
+        for sha256 in [
+            "zz",
+            "",
+            &"ab".repeat(31),
+            &"zz".repeat(32),
+            &"ab".repeat(33),
+        ] {
+            locked.sha256 = sha256.to_string();
+            assert!(locked.sha256_digest().is_err(), "{sha256}");
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_sha256() {
+        let dir = temp_dir("lock", "sha256");
+        let path = dir.join("grd.lock");
+        let mut lockfile = Lockfile::default();
+        let mut locked = locked_asset("v1");
+        locked.sha256 = "zz".to_string();
+        lockfile.insert("tool", "linux-x86_64", locked);
+        lockfile.save(&path).unwrap();
+        assert!(matches!(
+            Lockfile::load(&path),
+            Err(Error::InvalidManifest { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+//! A manifest (`grd.toml`) describes the tools installed on a machine, `grd
+//! sync` installs, updates and removes tools until they match it.
+//!
+//! ```toml
+//! [settings]
+//! bin-dir = "~/.local/bin"
+//!
+//! [tools.rg]
+//! repository = "github.com/BurntSushi/ripgrep"
+//! tag = "14.1.0"
+//! verify-checksums = true
+//!
+//! [tools.rg.asset-pattern]
+//! linux-x86_64 = "x86_64-unknown-linux-musl\\.tar\\.gz$"
+//! macos = "apple-darwin\\.tar\\.gz$"
+//!
+//! [tools.jq]
+//! repository = "github.com/jqlang/jq"
+//! version = "~1.7"
+//! tag-prefix = "jq-"
+//! asset-pattern = "jq-linux-amd64$"
+//! ```
+//!
+//! Tools are named by their table, which is the name they are installed under.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    arguments::{AssetArgs, VerifyArgs},
+    config::Config,
+    Error, GitWebsite, IpType,
+};
+
+mod lock;
+pub use lock::*;
+
+/// File name of the manifest `grd sync` reads by default.
+pub const DEFAULT_MANIFEST: &str = "grd.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub tools: BTreeMap<String, Tool>,
+}
+
+/// Install options shared by all tools of the manifest.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    pub bin_dir: Option<PathBuf>,
+    pub tools_dir: Option<PathBuf>,
+}
+
+/// A tool of the manifest, the fields correspond to the options of `grd install`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Tool {
+    pub repository: String,
+    /// Guessed from the repository if omitted, like on the command line.
+    pub website_type: Option<GitWebsite>,
+    pub ip_type: Option<IpType>,
+    #[serde(default)]
+    pub headers: Vec<String>,
+    pub asset_pattern: AssetPattern,
+    /// The release to install, the latest one if omitted.
+    pub tag: Option<String>,
+    /// Regex restricting the releases to the ones with a matching tag.
+    pub tag_pattern: Option<String>,
+    /// Semantic version requirement, the release with the highest matching version is installed.
+    pub version: Option<String>,
+    pub tag_prefix: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    pub binary: Option<String>,
+    #[serde(default)]
+    pub no_verify: bool,
+    pub verify_checksums: Option<ChecksumsSetting>,
+    pub minisign_key: Option<String>,
+    pub pgp_keyring: Option<PathBuf>,
+    #[serde(default)]
+    pub pgp_fingerprints: Vec<String>,
+    pub sigstore_trust_root: Option<PathBuf>,
+    pub sigstore_identity: Option<String>,
+    pub sigstore_issuer: Option<String>,
+    pub sigstore_bundle: Option<String>,
+}
+
+/// The platform `grd` is running on, as used for the keys of
+/// [`AssetPattern::PerPlatform`], e.g. "linux-x86_64".
+pub fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// The asset pattern of a tool, either one for all platforms or one per platform.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum AssetPattern {
+    All(String),
+    /// Keyed by "<os>-<arch>" or "<os>", using the names of
+    /// [`std::env::consts::OS`] and [`std::env::consts::ARCH`].
+    PerPlatform(BTreeMap<String, String>),
+}
+
+impl AssetPattern {
+    /// The pattern for the platform, "<os>-<arch>" is preferred over "<os>".
+    pub fn for_platform(&self, os: &str, arch: &str) -> Option<&str> {
+        match self {
+            AssetPattern::All(pattern) => Some(pattern),
+            AssetPattern::PerPlatform(patterns) => patterns
+                .get(&format!("{os}-{arch}"))
+                .or_else(|| patterns.get(os))
+                .map(String::as_str),
+        }
+    }
+}
+
+/// `verify-checksums = true` detects the checksum file, a string is its pattern.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ChecksumsSetting {
+    Enabled(bool),
+    Pattern(String),
+}
+
+// "~/" is expanded, relative paths are relative to the manifest
+fn resolve_path(path: &mut PathBuf, base_dir: &Path) {
+    if let Ok(relative) = path.strip_prefix("~") {
+        if let Some(home) = std::env::home_dir() {
+            *path = home.join(relative);
+        }
+    } else if path.is_relative() {
+        *path = base_dir.join(&*path);
+    }
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`, paths in it are resolved relative to its directory.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let invalid = |reason: String| Error::InvalidManifest {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let content = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let mut manifest: Manifest =
+            toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+        if let Some(name) = manifest
+            .tools
+            .keys()
+            .find(|name| !crate::install::is_valid_name(name))
+        {
+            return Err(invalid(format!("invalid tool name \"{name}\"")));
+        }
+        for (name, tool) in &manifest.tools {
+            if tool.tag.is_some() && (tool.version.is_some() || tool.tag_pattern.is_some()) {
+                return Err(invalid(format!(
+                    "\"{name}\" can not have a tag together with a version or tag pattern"
+                )));
+            }
+            if let Some(tag_pattern) = &tool.tag_pattern {
+                Regex::new(tag_pattern).map_err(|e| invalid(format!("\"{name}\": {e}")))?;
+            }
+            if let Some(version) = &tool.version {
+                crate::version::parse_requirement(version)
+                    .map_err(|e| invalid(format!("\"{name}\": {e}")))?;
+            }
+        }
+
+        let base_dir = std::path::absolute(path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let settings = &mut manifest.settings;
+        for dir in [&mut settings.bin_dir, &mut settings.tools_dir]
+            .into_iter()
+            .flatten()
+        {
+            resolve_path(dir, &base_dir);
+        }
+        for tool in manifest.tools.values_mut() {
+            for file in [&mut tool.pgp_keyring, &mut tool.sigstore_trust_root]
+                .into_iter()
+                .flatten()
+            {
+                resolve_path(file, &base_dir);
+            }
+            // the key may also be given as file
+            if let Some(key) = &mut tool.minisign_key {
+                let mut key_file = PathBuf::from(&*key);
+                resolve_path(&mut key_file, &base_dir);
+                if key_file.is_file() {
+                    *key = key_file.to_string_lossy().to_string();
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+impl Tool {
+    /// Selects the asset like the arguments of `grd install`, using the asset
+    /// pattern of the platform `grd` is running on.
+    pub fn asset_args(&self, name: &str, config: &Config) -> Result<AssetArgs, Error> {
+        let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+        let asset_pattern = self.asset_pattern.for_platform(os, arch).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "\"{name}\" has no asset pattern for the platform \"{os}-{arch}\""
+            ))
+        })?;
+        let repository = config.resolve_repository(
+            self.repository.clone(),
+            self.website_type.clone(),
+            self.ip_type,
+            self.headers.clone(),
+        )?;
+        Ok(AssetArgs {
+            repository,
+            asset_pattern: asset_pattern.to_string(),
+            tag: self.tag.clone(),
+            tag_pattern: self.tag_pattern.clone(),
+            version_requirement: self.version.clone(),
+            tag_prefix: self.tag_prefix.clone(),
+            allow_prerelease: self.prerelease,
+        })
+    }
+
+    pub fn verify_args(&self) -> VerifyArgs {
+        VerifyArgs {
+            no_verify: self.no_verify,
+            verify_checksums: match &self.verify_checksums {
+                None | Some(ChecksumsSetting::Enabled(false)) => None,
+                Some(ChecksumsSetting::Enabled(true)) => Some(None),
+                Some(ChecksumsSetting::Pattern(pattern)) => Some(Some(pattern.clone())),
+            },
+            minisign_key: self.minisign_key.clone(),
+            pgp_keyring: self.pgp_keyring.clone(),
+            pgp_fingerprints: self.pgp_fingerprints.clone(),
+            sigstore_trust_root: self.sigstore_trust_root.clone(),
+            sigstore_identity: self.sigstore_identity.clone(),
+            sigstore_issuer: self.sigstore_issuer.clone(),
+            sigstore_bundle: self.sigstore_bundle.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::temp_dir, ParseRepositoryError};
+
+    #[test]
+    fn test_asset_pattern_for_platform() {
+        let pattern: Manifest = toml::from_str(
+            r#"
+            [tools.tool]
+            repository = "github.com/owner/tool"
+            asset-pattern = { linux-x86_64 = "linux-amd64", linux = "linux", windows-x86_64 = "\\.exe$" }
+            "#,
+        )
+        .unwrap();
+        let pattern = &pattern.tools["tool"].asset_pattern;
+        assert_eq!(pattern.for_platform("linux", "x86_64"), Some("linux-amd64"));
+        assert_eq!(pattern.for_platform("linux", "aarch64"), Some("linux"));
+        assert_eq!(pattern.for_platform("macos", "aarch64"), None);
+        assert_eq!(
+            AssetPattern::All("tool".to_string()).for_platform("macos", "aarch64"),
+            Some("tool")
+        );
+    }
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = temp_dir("manifest", "load");
+        let path = dir.join(DEFAULT_MANIFEST);
+        fs::write(
+            &path,
+            r#"
+            [settings]
+            bin-dir = "bin"
+
+            [tools.rg]
+            repository = "github.com/BurntSushi/ripgrep"
+            asset-pattern = "musl"
+            tag = "14.1.0"
+            verify-checksums = true
+            pgp-keyring = "keys/upstream.asc"
+
+            [tools.forgejo]
+            repository = "codeberg.org/forgejo/forgejo"
+            website-type = "gitea"
+            ip-type = "ipv4"
+            asset-pattern = "linux-amd64$"
+            verify-checksums = "\\.sha256$"
+            "#,
+        )
+        .unwrap();
+        let manifest = Manifest::load(&path).unwrap();
+        assert_eq!(manifest.settings.bin_dir, Some(dir.join("bin")));
+        assert_eq!(manifest.settings.tools_dir, None);
+
+        let rg = &manifest.tools["rg"];
+        assert_eq!(rg.pgp_keyring, Some(dir.join("keys/upstream.asc")));
+        assert_eq!(rg.verify_args().verify_checksums, Some(None));
+        let asset_args = rg.asset_args("rg", &Config::default()).unwrap();
+        assert_eq!(asset_args.repository.website, GitWebsite::GitHub);
+        assert_eq!(asset_args.tag.as_deref(), Some("14.1.0"));
+
+        let forgejo = &manifest.tools["forgejo"];
+        assert_eq!(
+            forgejo.verify_args().verify_checksums,
+            Some(Some("\\.sha256$".to_string()))
+        );
+        let asset_args = forgejo.asset_args("forgejo", &Config::default()).unwrap();
+        assert_eq!(
+            asset_args.repository.canonical_string(),
+            "codeberg.org/forgejo/forgejo"
+        );
+        assert_eq!(asset_args.repository.ip_type, IpType::IPV4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let dir = temp_dir("manifest", "invalid");
+        let path = dir.join(DEFAULT_MANIFEST);
+        for content in [
+            "[tools.tool]\nrepository = \"github.com/owner/tool\"",
+            "[tools.tool]\nrepository = \"github.com/owner/tool\"\nasset-pattern = \"x\"\nunknown = 1",
+            "[tools.\"..\"]\nrepository = \"github.com/owner/tool\"\nasset-pattern = \"x\"",
+            "[tools.tool]\nrepository = \"github.com/owner/tool\"\nasset-pattern = \"x\"\nversion = \"1.4 or 2\"",
+            "[tools.tool]\nrepository = \"github.com/owner/tool\"\nasset-pattern = \"x\"\nversion = \"~1.4\"\ntag = \"v1.4.0\"",
+        ] {
+            fs::write(&path, content).unwrap();
+            assert!(matches!(
+                Manifest::load(&path),
+                Err(Error::InvalidManifest { .. })
+            ));
+        }
+        assert!(matches!(
+            Manifest::load(&dir.join("missing.toml")),
+            Err(Error::InvalidManifest { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_website_type_is_guessed() {
+        let manifest: Manifest = toml::from_str(
+            "[tools.tool]\nrepository = \"git.example.com/owner/tool\"\nasset-pattern = \"x\"",
+        )
+        .unwrap();
+        assert!(matches!(
+            manifest.tools["tool"].asset_args("tool", &Config::default()),
+            Err(Error::InvalidRepository(
+                ParseRepositoryError::UnknownWebsiteType(_)
+            ))
+        ));
+    }
+}
@@ -1,9 +1,28 @@
 use serde::Deserialize;
 
+// GitHub and Gitea share the same field names for releases and assets,
+// fields that only one of them (or neither, depending on the version) provides
+// are optional. Timestamps are kept as the RFC 3339 strings returned by the APIs.
+
 #[derive(Debug, Deserialize)]
 pub struct Release {
     pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
     pub prerelease: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    // branch name or commit sha the tag has been created from
+    #[serde(default)]
+    pub target_commitish: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
     pub assets: Vec<Asset>,
 }
 
@@ -12,11 +31,36 @@ pub struct Asset {
     pub browser_download_url: String,
     pub name: String,
     pub id: i64,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub download_count: Option<u64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    // in the form "<algorithm>:<hex digest>", e.g. "sha256:..."
+    #[serde(default)]
+    pub digest: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GitLabRelease {
     pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub released_at: Option<String>,
+    #[serde(default)]
+    pub commit: Option<GitLabCommit>,
+    #[serde(default, rename = "_links")]
+    pub links: Option<GitLabReleaseLinks>,
     pub upcoming_release: bool,
     pub assets: GitLabAssets,
 }
@@ -25,12 +69,31 @@ impl From<GitLabRelease> for Release {
     fn from(value: GitLabRelease) -> Self {
         Self {
             tag_name: value.tag_name,
+            name: value.name,
+            body: value.description,
+            // GitLab has no concept of draft releases
+            draft: false,
             prerelease: value.upcoming_release,
+            created_at: value.created_at,
+            published_at: value.released_at,
+            target_commitish: value.commit.map(|commit| commit.id),
+            html_url: value.links.and_then(|links| links.self_url),
             assets: value.assets.links.into_iter().map(Into::into).collect(),
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GitLabCommit {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitLabReleaseLinks {
+    #[serde(default, rename = "self")]
+    pub self_url: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GitLabAssets {
     pub links: Vec<GitLabAsset>,
@@ -45,10 +108,105 @@ pub struct GitLabAsset {
 
 impl From<GitLabAsset> for Asset {
     fn from(value: GitLabAsset) -> Self {
+        // release links are plain urls, GitLab does not know anything
+        // about the file behind them
         Self {
             browser_download_url: value.direct_asset_url,
             name: value.name,
             id: value.id,
+            size: None,
+            content_type: None,
+            download_count: None,
+            created_at: None,
+            updated_at: None,
+            digest: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_github_release() {
+        let json = r#"{
+            "tag_name": "v1.0.0",
+            "name": "Version 1.0.0",
+            "body": "changelog",
+            "draft": false,
+            "prerelease": false,
+            "created_at": "2024-01-01T00:00:00Z",
+            "published_at": "2024-01-02T00:00:00Z",
+            "target_commitish": "main",
+            "html_url": "https://github.com/owner/repo/releases/tag/v1.0.0",
+            "assets": [{
+                "browser_download_url": "https://github.com/owner/repo/releases/download/v1.0.0/tool.tar.gz",
+                "name": "tool.tar.gz",
+                "id": 42,
+                "size": 1024,
+                "content_type": "application/gzip",
+                "download_count": 7,
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:01Z",
+                "digest": "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            }]
+        }"#;
+        let release: Release = serde_json::from_str(json).unwrap();
+        assert_eq!(release.name.as_deref(), Some("Version 1.0.0"));
+        assert_eq!(release.target_commitish.as_deref(), Some("main"));
+        let asset = &release.assets[0];
+        assert_eq!(asset.size, Some(1024));
+        assert_eq!(asset.download_count, Some(7));
+        assert!(asset.digest.as_deref().unwrap().starts_with("sha256:"));
+    }
+
+    #[test]
+    fn test_deserialize_gitea_release_without_optional_fields() {
+        let json = r#"{
+            "tag_name": "v1.0.0",
+            "prerelease": true,
+            "assets": [{
+                "browser_download_url": "https://codeberg.org/owner/repo/releases/download/v1.0.0/tool",
+                "name": "tool",
+                "id": 1,
+                "size": 3
+            }]
+        }"#;
+        let release: Release = serde_json::from_str(json).unwrap();
+        assert!(!release.draft);
+        assert_eq!(release.published_at, None);
+        assert_eq!(release.assets[0].content_type, None);
+    }
+
+    #[test]
+    fn test_gitlab_release_into_release() {
+        let json = r#"{
+            "tag_name": "v1.0.0",
+            "name": "v1.0.0",
+            "description": "changelog",
+            "created_at": "2024-01-01T00:00:00Z",
+            "released_at": "2024-01-02T00:00:00Z",
+            "upcoming_release": false,
+            "commit": { "id": "0123abcd" },
+            "_links": { "self": "https://gitlab.com/owner/repo/-/releases/v1.0.0" },
+            "assets": { "links": [{
+                "name": "tool.apk",
+                "direct_asset_url": "https://gitlab.com/owner/repo/-/releases/v1.0.0/downloads/tool.apk",
+                "id": 5
+            }] }
+        }"#;
+        let release: Release = serde_json::from_str::<GitLabRelease>(json).unwrap().into();
+        assert_eq!(release.body.as_deref(), Some("changelog"));
+        assert_eq!(
+            release.published_at.as_deref(),
+            Some("2024-01-02T00:00:00Z")
+        );
+        assert_eq!(release.target_commitish.as_deref(), Some("0123abcd"));
+        assert_eq!(
+            release.html_url.as_deref(),
+            Some("https://gitlab.com/owner/repo/-/releases/v1.0.0")
+        );
+        assert_eq!(release.assets[0].size, None);
+    }
+}
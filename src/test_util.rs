@@ -0,0 +1,103 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::path::PathBuf;
+
+use crate::{
+    install::{InstalledVersion, Receipt},
+    models::{Asset, Release},
+    GitWebsite,
+};
+
+/// Writes `content` to a file in the temporary directory, named after the
+/// module (`prefix`), the process and `name`, so parallel tests do not collide.
+pub(crate) fn write_temp_file(prefix: &str, name: &str, content: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("grd-{prefix}-{}-{name}", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+/// A path in the temporary directory like [`write_temp_file`], removed if it
+/// exists, for tests which expect the directory to be created by the tested code.
+pub(crate) fn temp_path(prefix: &str, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("grd-{prefix}-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+/// An empty directory at [`temp_path`].
+pub(crate) fn temp_dir(prefix: &str, name: &str) -> PathBuf {
+    let path = temp_path(prefix, name);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// An asset without any of the optional metadata.
+pub(crate) fn asset(name: &str) -> Asset {
+    Asset {
+        browser_download_url: format!("https://example.com/{name}"),
+        name: name.to_string(),
+        id: 0,
+        size: None,
+        content_type: None,
+        download_count: None,
+        created_at: None,
+        updated_at: None,
+        digest: None,
+    }
+}
+
+/// A published release with the assets `asset_names`, numbered as their ids.
+pub(crate) fn release(tag_name: &str, prerelease: bool, asset_names: &[&str]) -> Release {
+    Release {
+        tag_name: tag_name.to_string(),
+        name: None,
+        body: None,
+        draft: false,
+        prerelease,
+        created_at: None,
+        published_at: None,
+        target_commitish: None,
+        html_url: None,
+        assets: asset_names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| Asset {
+                id: id as i64,
+                ..asset(name)
+            })
+            .collect(),
+    }
+}
+
+/// An installed version of a tool named "tool".
+pub(crate) fn installed_version(tag: &str) -> InstalledVersion {
+    InstalledVersion {
+        tag: tag.to_string(),
+        asset: format!("tool-{tag}.tar.gz"),
+        url: format!("https://example.com/{tag}/tool-{tag}.tar.gz"),
+        sha256: "00".repeat(32),
+        executable_sha256: None,
+        installed_at: "2024-02-29T13:05:00Z".to_string(),
+    }
+}
+
+/// The receipt of the tool `name` installed as `version`, without any files
+/// and versions.
+pub(crate) fn receipt(name: &str, version: InstalledVersion) -> Receipt {
+    Receipt {
+        name: name.to_string(),
+        repository: format!("github.com/owner/{name}"),
+        website: GitWebsite::GitHub,
+        asset_pattern: "linux".to_string(),
+        allow_prerelease: false,
+        tag_pattern: None,
+        version_requirement: None,
+        tag_prefix: None,
+        binary: None,
+        version,
+        files: vec![],
+        tool_dir: None,
+        pinned: false,
+        versions: vec![],
+    }
+}
@@ -0,0 +1,303 @@
+use regex::Regex;
+use ureq::Agent;
+
+use super::{Digest, DigestAlgorithm};
+use crate::{
+    download_asset_bytes,
+    models::{Asset, Release},
+    Error, Repository,
+};
+
+/// A line of a checksum file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    /// `None` if the file only contains the digest, like some per-file checksum files do.
+    pub file_name: Option<String>,
+    pub digest: Digest,
+}
+
+// extensions of checksum files that belong to a single asset, e.g. "tool.tar.gz.sha256"
+const PER_FILE_EXTENSIONS: &[&str] = &[
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sha512sum",
+    ".b3",
+    ".blake3",
+];
+
+// see get_github_optional_origin_and_repository_regex for notes to unwrapping Regex
+fn get_checksum_file_name_regex() -> Regex {
+    // matches e.g. SHA256SUMS, B3SUMS, checksums.txt, tool_1.0.0_checksums.txt, sha256sums.txt
+    Regex::new(r"(?i)(^|[^a-z])(sha256|sha512|b3|blake3)?(sums|checksums?)(\.txt)?$").unwrap()
+}
+
+/// Finds the checksum file for `asset` in `release`.
+///
+/// If `pattern` is passed, the first other asset matching it is used. Otherwise a
+/// per-file checksum (e.g. "tool.tar.gz.sha256") is preferred over a checksum file
+/// covering the whole release (e.g. "SHA256SUMS" or "checksums.txt").
+pub fn find_checksum_asset<'a>(
+    release: &'a Release,
+    asset: &Asset,
+    pattern: Option<&Regex>,
+) -> Option<&'a Asset> {
+    let mut candidates = release
+        .assets
+        .iter()
+        .filter(|candidate| candidate.name != asset.name);
+
+    if let Some(pattern) = pattern {
+        return candidates.find(|candidate| pattern.is_match(&candidate.name));
+    }
+
+    let per_file = release.assets.iter().find(|candidate| {
+        PER_FILE_EXTENSIONS.iter().any(|extension| {
+            candidate
+                .name
+                .strip_suffix(extension)
+                .is_some_and(|name| name == asset.name)
+        })
+    });
+    if per_file.is_some() {
+        return per_file;
+    }
+
+    let checksum_file_name_regex = get_checksum_file_name_regex();
+    candidates.find(|candidate| checksum_file_name_regex.is_match(&candidate.name))
+}
+
+// the algorithm is guessed from the name of the checksum file first,
+// since blake3 and sha256 digests have the same length. Only whole tokens of
+// the name count, so "fab3d-1.0.sha256sums" is not taken for a blake3 file.
+fn guess_algorithm(checksum_file_name: &str, hex_length: usize) -> Option<DigestAlgorithm> {
+    let lowercase_name = checksum_file_name.to_ascii_lowercase();
+    let has_token = |names: &[&str]| {
+        lowercase_name
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|token| {
+                names.iter().any(|name| {
+                    token
+                        .strip_prefix(name)
+                        .is_some_and(|suffix| ["", "sum", "sums"].contains(&suffix))
+                })
+            })
+    };
+    if has_token(&["sha512"]) {
+        return Some(DigestAlgorithm::Sha512);
+    }
+    if has_token(&["sha256"]) {
+        return Some(DigestAlgorithm::Sha256);
+    }
+    if has_token(&["b3", "blake3"]) {
+        return Some(DigestAlgorithm::Blake3);
+    }
+    match hex_length {
+        64 => Some(DigestAlgorithm::Sha256),
+        128 => Some(DigestAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+// see get_github_optional_origin_and_repository_regex for notes to unwrapping Regex
+fn get_bsd_checksum_line_regex() -> Regex {
+    // e.g. "SHA256 (tool.tar.gz) = <hex>"
+    Regex::new(
+        r"^(?P<algorithm>[A-Za-z0-9-]+) ?\((?P<file_name>.+)\) ?= ?(?P<digest>[0-9a-fA-F]+)$",
+    )
+    .unwrap()
+}
+
+fn get_gnu_checksum_line_regex() -> Regex {
+    // e.g. "<hex>  tool.tar.gz" or "<hex> *tool.tar.gz" (binary mode),
+    // per-file checksum files sometimes only contain the digest
+    Regex::new(r"^(?P<digest>[0-9a-fA-F]+)(\s+\*?(?P<file_name>.+))?$").unwrap()
+}
+
+/// Parses the content of a checksum file in GNU (`sha256sum`) or BSD (`sha256sum --tag`) format.
+///
+/// Lines that can not be parsed are skipped.
+pub fn parse_checksums(content: &str, checksum_file_name: &str) -> Vec<ChecksumEntry> {
+    let bsd_regex = get_bsd_checksum_line_regex();
+    let gnu_regex = get_gnu_checksum_line_regex();
+
+    let mut entries = vec![];
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (algorithm, file_name, hex_digest) = if let Some(captures) = bsd_regex.captures(line) {
+            let algorithm = captures["algorithm"].replace('-', "");
+            let Ok(algorithm) = algorithm.parse::<DigestAlgorithm>() else {
+                continue;
+            };
+            (
+                algorithm,
+                Some(captures["file_name"].to_string()),
+                captures["digest"].to_string(),
+            )
+        } else if let Some(captures) = gnu_regex.captures(line) {
+            let hex_digest = captures["digest"].to_string();
+            let Some(algorithm) = guess_algorithm(checksum_file_name, hex_digest.len()) else {
+                continue;
+            };
+            let file_name = captures
+                .name("file_name")
+                .map(|file_name| file_name.as_str().to_string());
+            (algorithm, file_name, hex_digest)
+        } else {
+            continue;
+        };
+
+        let Ok(value) = hex::decode(hex_digest) else {
+            continue;
+        };
+        entries.push(ChecksumEntry {
+            file_name,
+            digest: Digest { algorithm, value },
+        });
+    }
+    entries
+}
+
+/// Returns the digest listed for `asset_name`.
+///
+/// File names are compared without leading directories, since checksum files are
+/// often generated in a build directory (e.g. "./dist/tool.tar.gz").
+/// Entries without a file name only match if they are the only entry.
+pub fn find_checksum<'a>(entries: &'a [ChecksumEntry], asset_name: &str) -> Option<&'a Digest> {
+    if let [ChecksumEntry {
+        file_name: None,
+        digest,
+    }] = entries
+    {
+        return Some(digest);
+    }
+    entries
+        .iter()
+        .find(|entry| {
+            entry
+                .file_name
+                .as_deref()
+                .is_some_and(|file_name| file_name.rsplit(['/', '\\']).next() == Some(asset_name))
+        })
+        .map(|entry| &entry.digest)
+}
+
+/// Looks up the checksum of `asset` in a checksum file of `release`.
+///
+/// See [`find_checksum_asset`] for how the checksum file is selected.
+/// Returns the checksum asset along with the digest listed for `asset`.
+pub fn fetch_checksum<'a>(
+    agent: &Agent,
+    repository: &Repository,
+    release: &'a Release,
+    asset: &Asset,
+    pattern: Option<&Regex>,
+) -> Result<(&'a Asset, Digest), Error> {
+    let verification_error = |reason: String| Error::Verification {
+        asset: asset.name.clone(),
+        reason,
+    };
+
+    let checksum_asset = find_checksum_asset(release, asset, pattern).ok_or_else(|| {
+        verification_error(format!(
+            "no checksum file found in release \"{}\"",
+            release.tag_name
+        ))
+    })?;
+    let content = download_asset_bytes(agent, repository, checksum_asset)?;
+    let entries = parse_checksums(&String::from_utf8_lossy(&content), &checksum_asset.name);
+    let digest = find_checksum(&entries, &asset.name).ok_or_else(|| {
+        verification_error(format!(
+            "\"{}\" does not contain a checksum for it",
+            checksum_asset.name
+        ))
+    })?;
+    Ok((checksum_asset, digest.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{asset, release};
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_parse_gnu_checksums() {
+        let content = format!("{HELLO_SHA256}  tool.tar.gz\n{HELLO_SHA256} *./dist/tool.zip\n");
+        let entries = parse_checksums(&content, "SHA256SUMS");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file_name.as_deref(), Some("tool.tar.gz"));
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(
+            find_checksum(&entries, "tool.zip").unwrap().to_hex(),
+            HELLO_SHA256
+        );
+        assert!(find_checksum(&entries, "tool.deb").is_none());
+    }
+
+    #[test]
+    fn test_parse_bsd_checksums() {
+        let content =
+            format!("SHA256 (tool.tar.gz) = {HELLO_SHA256}\nBLAKE3 (tool.zip) = {HELLO_SHA256}\n");
+        let entries = parse_checksums(&content, "checksums.txt");
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(entries[1].digest.algorithm, DigestAlgorithm::Blake3);
+        assert_eq!(entries[1].file_name.as_deref(), Some("tool.zip"));
+    }
+
+    #[test]
+    fn test_parse_bare_digest_and_algorithm_guessing() {
+        let entries = parse_checksums(HELLO_SHA256, "tool.tar.gz.b3");
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Blake3);
+        assert!(find_checksum(&entries, "tool.tar.gz").is_some());
+
+        let entries = parse_checksums(HELLO_SHA256, "B3SUMS");
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Blake3);
+
+        // "b3" inside other words or next to a sha256 extension is not blake3
+        for name in ["tool-b3-linux.tar.gz.sha256", "fab3d-1.0.sha256sums"] {
+            let entries = parse_checksums(HELLO_SHA256, name);
+            assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Sha256);
+        }
+        let entries = parse_checksums(HELLO_SHA256, "fab3d-1.0-checksums.txt");
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Sha256);
+
+        let sha512 = "a".repeat(128);
+        let entries = parse_checksums(&format!("{sha512}  tool"), "checksums.txt");
+        assert_eq!(entries[0].digest.algorithm, DigestAlgorithm::Sha512);
+    }
+
+    #[test]
+    fn test_find_checksum_asset() {
+        let release = release(
+            "v1.0.0",
+            false,
+            &["tool.tar.gz", "tool.tar.gz.sha256", "SHA256SUMS"],
+        );
+        let found = find_checksum_asset(&release, &asset("tool.tar.gz"), None).unwrap();
+        assert_eq!(found.name, "tool.tar.gz.sha256");
+
+        let release = self::release(
+            "v1.0.0",
+            false,
+            &["tool.tar.gz", "tool_1.0.0_checksums.txt"],
+        );
+        let found = find_checksum_asset(&release, &asset("tool.tar.gz"), None).unwrap();
+        assert_eq!(found.name, "tool_1.0.0_checksums.txt");
+
+        let release = self::release("v1.0.0", false, &["tool.tar.gz", "tool.deb"]);
+        assert!(find_checksum_asset(&release, &asset("tool.tar.gz"), None).is_none());
+    }
+
+    #[test]
+    fn test_regex_compilations() {
+        get_checksum_file_name_regex();
+        get_bsd_checksum_line_regex();
+        get_gnu_checksum_line_regex();
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use sha2::{Digest as _, Sha256, Sha512};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl DigestAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha256",
+            DigestAlgorithm::Sha512 => "sha512",
+            DigestAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(DigestAlgorithm::Sha256),
+            "sha512" => Ok(DigestAlgorithm::Sha512),
+            "blake3" => Ok(DigestAlgorithm::Blake3),
+            _ => Err(format!("unsupported digest algorithm \"{s}\"")),
+        }
+    }
+}
+
+/// A hash value together with the algorithm that produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub algorithm: DigestAlgorithm,
+    pub value: Vec<u8>,
+}
+
+impl Digest {
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.value)
+    }
+}
+
+// formatted like the digests of the GitHub api: "<algorithm>:<hex value>"
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algorithm.name(), self.to_hex())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("digest \"{s}\" must be in the form \"algorithm:value\""))?;
+        let algorithm = algorithm.parse()?;
+        let value = hex::decode(value).map_err(|e| format!("invalid digest \"{s}\": {e}"))?;
+        Ok(Digest { algorithm, value })
+    }
+}
+
+enum HasherState {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    // boxed, since the blake3 state is a lot larger than the others
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// Incrementally computes a [`Digest`].
+pub struct Hasher(HasherState);
+
+impl Hasher {
+    pub fn new(algorithm: DigestAlgorithm) -> Self {
+        Self(match algorithm {
+            DigestAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+            DigestAlgorithm::Sha512 => HasherState::Sha512(Sha512::new()),
+            DigestAlgorithm::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+        })
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.0 {
+            HasherState::Sha256(hasher) => hasher.update(data),
+            HasherState::Sha512(hasher) => hasher.update(data),
+            HasherState::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    pub fn finish(self) -> Digest {
+        match self.0 {
+            HasherState::Sha256(hasher) => Digest {
+                algorithm: DigestAlgorithm::Sha256,
+                value: hasher.finalize().to_vec(),
+            },
+            HasherState::Sha512(hasher) => Digest {
+                algorithm: DigestAlgorithm::Sha512,
+                value: hasher.finalize().to_vec(),
+            },
+            HasherState::Blake3(hasher) => Digest {
+                algorithm: DigestAlgorithm::Blake3,
+                value: hasher.finalize().as_bytes().to_vec(),
+            },
+        }
+    }
+}
+
+/// Writer that hashes everything written through it before passing it on.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hashers: Vec<Hasher>,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W, algorithms: &[DigestAlgorithm]) -> Self {
+        Self {
+            inner,
+            hashers: algorithms.iter().copied().map(Hasher::new).collect(),
+        }
+    }
+
+    /// Returns the digests in the order the algorithms have been passed to [`HashingWriter::new`].
+    pub fn finish(self) -> (W, Vec<Digest>) {
+        let digests = self.hashers.into_iter().map(Hasher::finish).collect();
+        (self.inner, digests)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        for hasher in &mut self.hashers {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hashes everything read from the inner reader, used when the content is
+/// consumed as a stream instead of being written to a file.
+pub struct HashingReader<R: Read> {
+    inner: R,
+    hashers: Vec<Hasher>,
+    bytes_read: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, algorithms: &[DigestAlgorithm]) -> Self {
+        Self {
+            inner,
+            hashers: algorithms.iter().copied().map(Hasher::new).collect(),
+            bytes_read: 0,
+        }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the digests in the order the algorithms have been passed to [`HashingReader::new`].
+    pub fn finish(self) -> (R, Vec<Digest>) {
+        let digests = self.hashers.into_iter().map(Hasher::finish).collect();
+        (self.inner, digests)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for hasher in &mut self.hashers {
+            hasher.update(&buf[..read]);
+        }
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
+/// Checks that every expected digest is contained in `actual`.
+///
+/// `actual` must contain a digest for every algorithm used by `expected`.
+pub fn check_digests(actual: &[Digest], expected: &[Digest]) -> Result<(), String> {
+    for expected in expected {
+        let actual = actual
+            .iter()
+            .find(|digest| digest.algorithm == expected.algorithm)
+            .ok_or_else(|| format!("{} digest has not been computed", expected.algorithm.name()))?;
+        if actual != expected {
+            return Err(format!("expected digest {expected}, got {actual}"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_parse_and_display_digest() {
+        let digest: Digest = format!("sha256:{EMPTY_SHA256}").parse().unwrap();
+        assert_eq!(digest.algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(digest.to_string(), format!("sha256:{EMPTY_SHA256}"));
+        assert!("md5:abcd".parse::<Digest>().is_err());
+        assert!("sha256".parse::<Digest>().is_err());
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let mut writer = HashingWriter::new(
+            Vec::new(),
+            &[DigestAlgorithm::Sha256, DigestAlgorithm::Sha512],
+        );
+        writer.write_all(b"hello").unwrap();
+        let (inner, digests) = writer.finish();
+        assert_eq!(inner, b"hello");
+        assert_eq!(
+            digests[0].to_hex(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(digests[1].algorithm, DigestAlgorithm::Sha512);
+    }
+
+    #[test]
+    fn test_hashing_reader() {
+        let mut reader = HashingReader::new(&b"hello"[..], &[DigestAlgorithm::Sha256]);
+        let mut content = vec![];
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!(reader.bytes_read(), 5);
+        let (_, digests) = reader.finish();
+        assert_eq!(
+            digests[0].to_hex(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::{fs, fs::File, io::Read, path::Path};
+
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::models::{Asset, Release};
+
+// both minisign and signify keys (and the first line of their signatures)
+// start with the algorithm "Ed" followed by an 8 byte key id
+const ALGORITHM_LENGTH: usize = 2;
+const KEY_ID_LENGTH: usize = 8;
+
+/// A minisign or signify public key.
+#[derive(Debug, Clone)]
+pub struct MinisignPublicKey {
+    // base64 of algorithm, key id and key, as used by minisign
+    base64: String,
+}
+
+impl MinisignPublicKey {
+    /// Parses the base64 encoded key or reads it from a `minisign.pub`/signify `.pub` file.
+    pub fn parse(key_or_file: &str) -> Result<Self, String> {
+        let base64 = if Path::new(key_or_file).is_file() {
+            let content = fs::read_to_string(key_or_file)
+                .map_err(|e| format!("could not read public key file \"{key_or_file}\": {e}"))?;
+            // the key is on the line following the untrusted comment
+            content
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+                .ok_or_else(|| format!("\"{key_or_file}\" does not contain a public key"))?
+                .to_string()
+        } else {
+            key_or_file.trim().to_string()
+        };
+        // validate early, so an invalid key is reported before anything is downloaded
+        minisign_verify::PublicKey::from_base64(&base64)
+            .map_err(|e| format!("invalid minisign public key: {e}"))?;
+        Ok(Self { base64 })
+    }
+
+    fn decode(&self) -> Vec<u8> {
+        // has been validated in parse
+        base64::engine::general_purpose::STANDARD
+            .decode(&self.base64)
+            .unwrap()
+    }
+}
+
+/// Finds the signature for `asset` in `release`, "<asset>.minisig" is preferred over "<asset>.sig".
+pub fn find_minisign_signature_asset<'a>(release: &'a Release, asset: &Asset) -> Option<&'a Asset> {
+    [".minisig", ".sig"].iter().find_map(|extension| {
+        let signature_name = format!("{}{extension}", asset.name);
+        release
+            .assets
+            .iter()
+            .find(|candidate| candidate.name == signature_name)
+    })
+}
+
+/// Verifies the file at `path` against a minisign or signify signature.
+///
+/// For minisign signatures the trusted comment is returned after it has been
+/// verified. If it names a file (`file:<name>`), that name must be `asset_name`,
+/// so a validly signed, but different file can not be passed off as the asset.
+pub fn verify_minisign(
+    public_key: &MinisignPublicKey,
+    signature: &str,
+    path: &Path,
+    asset_name: &str,
+) -> Result<Option<String>, String> {
+    let is_minisign = signature
+        .lines()
+        .any(|line| line.starts_with("trusted comment: "));
+    if !is_minisign {
+        verify_signify(public_key, signature, path)?;
+        return Ok(None);
+    }
+
+    let key = minisign_verify::PublicKey::from_base64(&public_key.base64)
+        .map_err(|e| format!("invalid minisign public key: {e}"))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|e| format!("invalid minisign signature: {e}"))?;
+
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let result = match key.verify_stream(&signature) {
+        Ok(mut verifier) => {
+            let mut buffer = [0_u8; 8192];
+            loop {
+                let read_size = file.read(&mut buffer).map_err(|e| e.to_string())?;
+                if read_size == 0 {
+                    break;
+                }
+                verifier.update(&buffer[..read_size]);
+            }
+            verifier.finalize()
+        }
+        // legacy signatures are made over the whole file instead of its hash
+        Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+            let mut content = vec![];
+            file.read_to_end(&mut content).map_err(|e| e.to_string())?;
+            key.verify(&content, &signature, true)
+        }
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| format!("minisign signature verification failed: {e}"))?;
+
+    let trusted_comment = signature.trusted_comment().to_string();
+    let signed_file_name = trusted_comment
+        .split(['\t', ' '])
+        .find_map(|part| part.strip_prefix("file:"));
+    if let Some(signed_file_name) = signed_file_name {
+        if signed_file_name != asset_name {
+            return Err(format!(
+                "signature has been made for \"{signed_file_name}\", not \"{asset_name}\""
+            ));
+        }
+    }
+    Ok(Some(trusted_comment))
+}
+
+// signify signatures consist of an untrusted comment and the base64 encoded
+// algorithm, key id and plain ed25519 signature over the whole file
+fn verify_signify(
+    public_key: &MinisignPublicKey,
+    signature: &str,
+    path: &Path,
+) -> Result<(), String> {
+    let invalid_signature = || "invalid signify signature".to_string();
+
+    let signature_base64 = signature
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or_else(invalid_signature)?;
+    let signature_bytes = base64::engine::general_purpose::STANDARD
+        .decode(signature_base64)
+        .map_err(|_| invalid_signature())?;
+    if signature_bytes.len() != ALGORITHM_LENGTH + KEY_ID_LENGTH + 64
+        || &signature_bytes[..ALGORITHM_LENGTH] != b"Ed"
+    {
+        return Err(invalid_signature());
+    }
+
+    let key_bytes = public_key.decode();
+    let key_id_range = ALGORITHM_LENGTH..ALGORITHM_LENGTH + KEY_ID_LENGTH;
+    if key_bytes[key_id_range.clone()] != signature_bytes[key_id_range] {
+        return Err("signature has been made with a different key".to_string());
+    }
+
+    // lengths have been checked above and in MinisignPublicKey::parse
+    let key = VerifyingKey::from_bytes(
+        key_bytes[ALGORITHM_LENGTH + KEY_ID_LENGTH..]
+            .try_into()
+            .unwrap(),
+    )
+    .map_err(|e| format!("invalid signify public key: {e}"))?;
+    let signature = Signature::from_bytes(
+        signature_bytes[ALGORITHM_LENGTH + KEY_ID_LENGTH..]
+            .try_into()
+            .unwrap(),
+    );
+
+    let content = fs::read(path).map_err(|e| e.to_string())?;
+    key.verify_strict(&content, &signature)
+        .map_err(|e| format!("signify signature verification failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_temp_file;
+
+    // taken from the tests of the minisign-verify crate
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==";
+
+    #[test]
+    fn test_verify_minisign() {
+        let key = MinisignPublicKey::parse(PUBLIC_KEY).unwrap();
+
+        let path = write_temp_file("minisign", "valid", b"test");
+        let trusted_comment = verify_minisign(&key, SIGNATURE, &path, "test").unwrap();
+        assert_eq!(
+            trusted_comment.as_deref(),
+            Some("timestamp:1555779966\tfile:test")
+        );
+        // the signature is valid, but has been made for a different file name
+        assert!(verify_minisign(&key, SIGNATURE, &path, "other").is_err());
+        fs::remove_file(&path).unwrap();
+
+        let path = write_temp_file("minisign", "tampered", b"Test");
+        assert!(verify_minisign(&key, SIGNATURE, &path, "test").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_signify() {
+        let key =
+            MinisignPublicKey::parse("RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4")
+                .unwrap();
+        let signature = "untrusted comment: verify with test.pub
+RWQBAgMEBQYHCKVXp6pguhWceWrRkO1fvuc88dyHDQ5ampsF9laj0O5bSvJ6m1nBrK8ZEpl5M50JqGgPmEJsV9DTs+MuMKH5/gk=
+";
+        let path = write_temp_file("minisign", "signify", b"test");
+        assert_eq!(verify_minisign(&key, signature, &path, "test"), Ok(None));
+        fs::write(&path, b"Test").unwrap();
+        assert!(verify_minisign(&key, signature, &path, "test").is_err());
+        fs::remove_file(&path).unwrap();
+
+        // minisign key with a different key id
+        let other_key = MinisignPublicKey::parse(PUBLIC_KEY).unwrap();
+        let path = write_temp_file("minisign", "signify-other-key", b"test");
+        assert!(verify_minisign(&other_key, signature, &path, "test").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_invalid_key() {
+        assert!(MinisignPublicKey::parse("not a key").is_err());
+    }
+}
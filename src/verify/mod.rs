@@ -0,0 +1,141 @@
+//! Verification of downloaded assets.
+
+use std::path::Path;
+
+use ureq::Agent;
+
+use crate::{
+    download_asset_bytes,
+    models::{Asset, Release},
+    Error, Repository,
+};
+
+mod digest;
+pub use digest::*;
+
+mod checksums;
+pub use checksums::*;
+
+mod minisign;
+pub use minisign::*;
+
+mod openpgp;
+pub use openpgp::*;
+
+mod sigstore;
+pub use sigstore::*;
+
+/// A signature scheme downloads can be verified with.
+#[derive(Debug)]
+pub enum SignatureVerifier {
+    Minisign(MinisignPublicKey),
+    OpenPgp(PgpKeyring),
+    Sigstore(SigstoreVerifier),
+}
+
+impl SignatureVerifier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SignatureVerifier::Minisign(_) => "minisign",
+            SignatureVerifier::OpenPgp(_) => "OpenPGP",
+            SignatureVerifier::Sigstore(_) => "sigstore",
+        }
+    }
+
+    /// Finds the asset containing the signature of `asset` for this scheme.
+    pub fn find_signature_asset<'a>(
+        &self,
+        release: &'a Release,
+        asset: &Asset,
+    ) -> Option<&'a Asset> {
+        match self {
+            SignatureVerifier::Minisign(_) => find_minisign_signature_asset(release, asset),
+            SignatureVerifier::OpenPgp(_) => find_pgp_signature_asset(release, asset),
+            SignatureVerifier::Sigstore(verifier) => {
+                find_sigstore_bundle_asset(release, asset, verifier.bundle_pattern.as_ref())
+            }
+        }
+    }
+
+    /// Verifies the file at `path`, returns a description of what has been verified.
+    pub fn verify(
+        &self,
+        signature: &[u8],
+        path: &Path,
+        asset_name: &str,
+    ) -> Result<String, String> {
+        match self {
+            SignatureVerifier::Minisign(key) => {
+                let signature = String::from_utf8_lossy(signature);
+                let trusted_comment = verify_minisign(key, &signature, path, asset_name)?;
+                Ok(match trusted_comment {
+                    Some(trusted_comment) => {
+                        format!("minisign signature, trusted comment: {trusted_comment}")
+                    }
+                    None => "signify signature".to_string(),
+                })
+            }
+            SignatureVerifier::OpenPgp(keyring) => {
+                let fingerprint = verify_pgp(keyring, signature, path)?;
+                Ok(format!("OpenPGP signature made by key {fingerprint}"))
+            }
+            SignatureVerifier::Sigstore(verifier) => {
+                let identity = verifier.verify(signature, path)?;
+                Ok(format!("sigstore bundle signed by {identity}"))
+            }
+        }
+    }
+}
+
+/// Downloads the signatures of `asset` for every verifier.
+///
+/// This is meant to be called before downloading the asset itself,
+/// so a missing signature does not waste a potentially large download.
+pub fn fetch_signatures<'v>(
+    agent: &Agent,
+    repository: &Repository,
+    release: &Release,
+    asset: &Asset,
+    verifiers: &'v [SignatureVerifier],
+) -> Result<Vec<(&'v SignatureVerifier, Vec<u8>)>, Error> {
+    verifiers
+        .iter()
+        .map(|verifier| {
+            let signature_asset =
+                verifier
+                    .find_signature_asset(release, asset)
+                    .ok_or_else(|| Error::Verification {
+                        asset: asset.name.clone(),
+                        reason: format!("no {} signature found in release", verifier.name()),
+                    })?;
+            let signature = download_asset_bytes(agent, repository, signature_asset)?;
+            Ok((verifier, signature))
+        })
+        .collect()
+}
+
+/// Verifies the downloaded file at `path` against the signatures from [`fetch_signatures`].
+///
+/// If a signature does not verify, the file is deleted.
+/// Returns a description for every verified signature.
+pub fn verify_signatures(
+    signatures: &[(&SignatureVerifier, Vec<u8>)],
+    path: &Path,
+    asset_name: &str,
+) -> Result<Vec<String>, Error> {
+    let mut verified = vec![];
+    for (verifier, signature) in signatures {
+        match verifier.verify(signature, path, asset_name) {
+            Ok(description) => verified.push(description),
+            Err(reason) => {
+                // a file that failed verification must not be left behind
+                std::fs::remove_file(path)?;
+                return Err(Error::Verification {
+                    asset: asset_name.to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(verified)
+}
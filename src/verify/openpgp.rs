@@ -0,0 +1,384 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use pgp::{
+    composed::{Deserializable, DetachedSignature, SignedPublicKey, SignedPublicSubKey},
+    packet::{Signature, SignatureType, SubpacketData},
+    types::{KeyDetails, Timestamp},
+};
+
+use crate::models::{Asset, Release};
+
+/// OpenPGP public keys signatures are verified against.
+#[derive(Debug)]
+pub struct PgpKeyring {
+    keys: Vec<SignedPublicKey>,
+}
+
+// fingerprints are compared as uppercase hex without the
+// spaces gpg uses to group them
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+impl PgpKeyring {
+    /// Reads all (armored or binary) public keys from `path`.
+    ///
+    /// Keys whose self-signatures are invalid are ignored.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("could not open keyring \"{}\": {e}", path.display()))?;
+        let (keys, _) = SignedPublicKey::from_reader_many(BufReader::new(file))
+            .map_err(|e| format!("could not read keyring \"{}\": {e}", path.display()))?;
+        let keys: Vec<_> = keys
+            .filter_map(Result::ok)
+            .filter(|key| key.verify_bindings().is_ok())
+            .collect();
+        if keys.is_empty() {
+            return Err(format!(
+                "keyring \"{}\" does not contain any valid public key",
+                path.display()
+            ));
+        }
+        Ok(Self { keys })
+    }
+
+    /// Only keeps the keys with the given primary key fingerprints.
+    ///
+    /// Fails if one of the fingerprints is not part of the keyring, since that is
+    /// most likely a mistake in the configuration.
+    pub fn pin(&mut self, fingerprints: &[String]) -> Result<(), String> {
+        let fingerprints: Vec<_> = fingerprints
+            .iter()
+            .map(|fingerprint| normalize_fingerprint(fingerprint))
+            .collect();
+        for fingerprint in &fingerprints {
+            let is_in_keyring = self
+                .keys
+                .iter()
+                .any(|key| &format!("{:X}", key.fingerprint()) == fingerprint);
+            if !is_in_keyring {
+                return Err(format!(
+                    "pinned key {fingerprint} is not part of the keyring"
+                ));
+            }
+        }
+        self.keys
+            .retain(|key| fingerprints.contains(&format!("{:X}", key.fingerprint())));
+        Ok(())
+    }
+}
+
+/// Finds the detached signature for `asset` in `release`.
+///
+/// "<asset>.asc" is preferred over "<asset>.sig" and "<asset>.gpg".
+pub fn find_pgp_signature_asset<'a>(release: &'a Release, asset: &Asset) -> Option<&'a Asset> {
+    [".asc", ".sig", ".gpg"].iter().find_map(|extension| {
+        let signature_name = format!("{}{extension}", asset.name);
+        release
+            .assets
+            .iter()
+            .find(|candidate| candidate.name == signature_name)
+    })
+}
+
+// the newest self-signature decides about the expiration and flags of a key
+fn newest_signature<'a>(signatures: impl Iterator<Item = &'a Signature>) -> Option<&'a Signature> {
+    signatures.max_by_key(|signature| signature.created().map(Timestamp::as_secs))
+}
+
+// checks that a key had neither been revoked nor expired when the signature was made
+fn check_key_validity(
+    description: &str,
+    created_at: Timestamp,
+    self_signature: Option<&Signature>,
+    is_revoked: bool,
+    signed_at: Timestamp,
+) -> Result<(), String> {
+    if is_revoked {
+        return Err(format!("the {description} has been revoked"));
+    }
+    let self_signature =
+        self_signature.ok_or_else(|| format!("the {description} has no self-signature"))?;
+    if let Some(expiration) = self_signature.key_expiration_time() {
+        let expiration = std::time::Duration::from(expiration).as_secs();
+        // an expiration of 0 means the key does not expire
+        let expires_at = u64::from(created_at.as_secs()) + expiration;
+        if expiration > 0 && u64::from(signed_at.as_secs()) > expires_at {
+            return Err(format!(
+                "the {description} had expired when the signature was made"
+            ));
+        }
+    }
+    Ok(())
+}
+
+// `makes_signature` is false if one of its subkeys made the signature
+fn check_primary_key(
+    key: &SignedPublicKey,
+    makes_signature: bool,
+    signed_at: Timestamp,
+) -> Result<(), String> {
+    let self_signatures = key
+        .details
+        .direct_signatures
+        .iter()
+        .chain(key.details.users.iter().flat_map(|user| &user.signatures));
+    let self_signature = newest_signature(self_signatures);
+    check_key_validity(
+        "key",
+        key.primary_key.created_at(),
+        self_signature,
+        !key.details.revocation_signatures.is_empty(),
+        signed_at,
+    )?;
+    // keys without key flags predate them and may be used for anything
+    let has_key_flags = self_signature
+        .and_then(Signature::config)
+        .is_some_and(|config| {
+            config
+                .hashed_subpackets()
+                .any(|subpacket| matches!(subpacket.data, SubpacketData::KeyFlags(_)))
+        });
+    if makes_signature
+        && has_key_flags
+        && !self_signature.is_some_and(|signature| signature.key_flags().sign())
+    {
+        return Err("the key is not allowed to make signatures".to_string());
+    }
+    Ok(())
+}
+
+fn check_subkey(subkey: &SignedPublicSubKey, signed_at: Timestamp) -> Result<(), String> {
+    let has_type = |signature: &Signature, typ| signature.typ() == Some(typ);
+    let binding = newest_signature(
+        subkey
+            .signatures
+            .iter()
+            .filter(|signature| has_type(signature, SignatureType::SubkeyBinding)),
+    );
+    let is_revoked = subkey
+        .signatures
+        .iter()
+        .any(|signature| has_type(signature, SignatureType::SubkeyRevocation));
+    check_key_validity(
+        "subkey",
+        subkey.key.created_at(),
+        binding,
+        is_revoked,
+        signed_at,
+    )?;
+    // only the binding of a signing subkey contains the signature of the
+    // subkey over the primary key, which proves that they belong together
+    if !binding.is_some_and(|binding| binding.key_flags().sign()) {
+        return Err("the subkey is not allowed to make signatures".to_string());
+    }
+    Ok(())
+}
+
+/// Verifies the file at `path` against an (armored or binary) detached signature.
+///
+/// Keys that have been revoked, that had expired when the signature was made or
+/// that are not allowed to make signatures are rejected.
+///
+/// Returns the fingerprint of the primary key that made the signature.
+pub fn verify_pgp(keyring: &PgpKeyring, signature: &[u8], path: &Path) -> Result<String, String> {
+    let (signatures, _) = DetachedSignature::from_reader_many(signature)
+        .map_err(|e| format!("invalid OpenPGP signature: {e}"))?;
+
+    let mut last_error = "signature has not been made by any key of the keyring".to_string();
+    for signature in signatures {
+        let signature = signature.map_err(|e| format!("invalid OpenPGP signature: {e}"))?;
+        let issuer_key_ids = signature.signature.issuer_key_id();
+        let issuer_fingerprints = signature.signature.issuer_fingerprint();
+        let signed_at = signature
+            .signature
+            .created()
+            .ok_or_else(|| "OpenPGP signature has no creation time".to_string())?;
+
+        for key in &keyring.keys {
+            let is_issuer = |details: &dyn KeyDetails| {
+                issuer_fingerprints.contains(&&details.fingerprint())
+                    || issuer_key_ids.contains(&&details.legacy_key_id())
+            };
+
+            // signatures are usually made by a signing subkey,
+            // but may also be made by the primary key
+            let result = if is_issuer(&key.primary_key) {
+                if let Err(e) = check_primary_key(key, true, signed_at) {
+                    last_error = e;
+                    continue;
+                }
+                let file = File::open(path).map_err(|e| e.to_string())?;
+                Some(
+                    signature
+                        .signature
+                        .verify(&key.primary_key, BufReader::new(file)),
+                )
+            } else {
+                match key
+                    .public_subkeys
+                    .iter()
+                    .find(|subkey| is_issuer(&subkey.key))
+                {
+                    Some(subkey) => {
+                        // the binding proves that the subkey belongs to the primary key
+                        if let Err(e) = subkey.verify_bindings(&key.primary_key) {
+                            last_error = format!("invalid subkey binding: {e}");
+                            continue;
+                        }
+                        // a revoked or expired primary key invalidates its subkeys
+                        if let Err(e) = check_primary_key(key, false, signed_at)
+                            .and_then(|()| check_subkey(subkey, signed_at))
+                        {
+                            last_error = e;
+                            continue;
+                        }
+                        let file = File::open(path).map_err(|e| e.to_string())?;
+                        Some(
+                            signature
+                                .signature
+                                .verify(&subkey.key, BufReader::new(file)),
+                        )
+                    }
+                    None => None,
+                }
+            };
+
+            match result {
+                Some(Ok(())) => return Ok(format!("{:X}", key.fingerprint())),
+                Some(Err(e)) => last_error = format!("OpenPGP signature verification failed: {e}"),
+                None => {}
+            }
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_temp_file;
+
+    // generated with gpg for this test, the signature has been made over "test"
+    const PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatTG4RYJKwYBBAHaRw8BAQdA/Se8TyOU4H+/xG1LwJgEo6jDiSLmo4H3zmeX
+LkoUhea0G2dyZCB0ZXN0IDx0ZXN0QGV4YW1wbGUuY29tPoiQBBMWCAA4FiEEnyYs
+aoah82m3eaN+fCXlXSLTjWUFAmrUxuECGwMFCwkIBwIGFQoJCAsCBBYCAwECHgEC
+F4AACgkQfCXlXSLTjWXI5AD8CTT3SRT0H9sXjbX80rvQvR2EY2zrO/L5eAbiPk1R
++54A/icpYsqcUoI78YtJMweedy6Cf8RUNEGw9b2dhdzzK6MC
+=UOcz
+-----END PGP PUBLIC KEY BLOCK-----";
+    const SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQSfJixqhqHzabd5o358JeVdItONZQUCatTG4QAKCRB8JeVdItON
+ZbkBAP9ZoXopSLPpdRPfWH4dehiGeaBek4HNPjcVwvhfOWmvIgD/T7dKzev2P/Jg
+v6K7dnJmFZ0QX7sFhsOMRrxgweq3NAU=
+=yV9A
+-----END PGP SIGNATURE-----";
+    const FINGERPRINT: &str = "9F262C6A86A1F369B779A37E7C25E55D22D38D65";
+    // revoked after the signature over "test" had been made
+    const REVOKED_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatTrDhYJKwYBBAHaRw8BAQdAHlsxJ602m4nLjAClvWNNhZ4WwWjaFEgtNIy1
+t4EcRBGIeAQgFggAIBYhBOj64CU5n3KeAe7UoBCmG/n7G2m5BQJq1OsOAh0AAAoJ
+EBCmG/n7G2m5bGkBAIHKMLRpl1Lb5FJiCppx7SQRan0+ccEtHbEri0/icZxSAQDS
+1Lnfbb4b760Cyg8++KnaN0fMn9K9dpD3L09ullEHArQhZ3JkIHJldm9rZWQgPHJl
+dm9rZWRAZXhhbXBsZS5jb20+iJAEExYIADgWIQTo+uAlOZ9yngHu1KAQphv5+xtp
+uQUCatTrDgIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRAQphv5+xtpubGq
+AQDlelE1UPjTnbEqywMCaqw6NOI2eYIG1hH2wNH+3AIhawEAxfViLLCu60QOHkUA
+vMy0qxWPQ45hkG5zneqZTPprAwk=
+=ey0v
+-----END PGP PUBLIC KEY BLOCK-----";
+    const REVOKED_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQTo+uAlOZ9yngHu1KAQphv5+xtpuQUCatTrDgAKCRAQphv5+xtp
+uWLkAQCV4ms1+jRZPr2hNK/o5dpbY1pXBG1kzy2QxxqHkROtUwEAg1O5i7YJ+K5i
+JlSWySuQk95S3ezcQiqFa4pU+j7OtQk=
+=djuq
+-----END PGP SIGNATURE-----";
+    // created on 2020-01-01 and valid for a day, the signature over "test"
+    // has been made on 2020-01-03
+    const EXPIRED_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEXgvhABYJKwYBBAHaRw8BAQdAp/MHd8eXIaclkfI0KaOH5dpRz0FmBKa/Ze/p
+uqOGh6e0IWdyZCBleHBpcmVkIDxleHBpcmVkQGV4YW1wbGUuY29tPoiWBBMWCAA+
+AhsDBQsJCAcCBhUKCQgLAgQWAgMBAh4BAheAFiEE7olqvr3kmmK9oZ92LmfzATO7
+eb4FAl4L7xAFCQABX5AACgkQLmfzATO7eb5NdwD7B7toVBCTnWQh2TeDpTXY0ojV
+1HbuhagCTO/58ElKLGgBAMN0KpJzbMrMecbl3wUbDbfuwcuKueopM/6MMpyVyoEA
+=QPd3
+-----END PGP PUBLIC KEY BLOCK-----";
+    const EXPIRED_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQTuiWq+veSaYr2hn3YuZ/MBM7t5vgUCXg6EAAAKCRAuZ/MBM7t5
+vs/IAQCpb81moBltiXEXBH0n0L83RftI7JsUZstecvhoL7S/qgEA+6DnKDz7pRAD
+tJHMWATzhOPf0ELFAnbeWzmhTP0ByQs=
+=Yv9P
+-----END PGP SIGNATURE-----";
+
+    #[test]
+    fn test_verify_pgp() {
+        let keyring_path = write_temp_file("openpgp", "keyring.asc", PUBLIC_KEY.as_bytes());
+        let keyring = PgpKeyring::from_file(&keyring_path).unwrap();
+
+        let path = write_temp_file("openpgp", "valid", b"test");
+        assert_eq!(
+            verify_pgp(&keyring, SIGNATURE.as_bytes(), &path).as_deref(),
+            Ok(FINGERPRINT)
+        );
+        std::fs::write(&path, b"Test").unwrap();
+        assert!(verify_pgp(&keyring, SIGNATURE.as_bytes(), &path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&keyring_path).unwrap();
+    }
+
+    #[test]
+    fn test_reject_revoked_and_expired_keys() {
+        let path = write_temp_file("openpgp", "unusable", b"test");
+        for (name, public_key, signature, reason) in [
+            (
+                "revoked",
+                REVOKED_PUBLIC_KEY,
+                REVOKED_SIGNATURE,
+                "the key has been revoked",
+            ),
+            (
+                "expired",
+                EXPIRED_PUBLIC_KEY,
+                EXPIRED_SIGNATURE,
+                "the key had expired when the signature was made",
+            ),
+        ] {
+            let keyring_path = write_temp_file(
+                "openpgp",
+                &format!("keyring-{name}.asc"),
+                public_key.as_bytes(),
+            );
+            let keyring = PgpKeyring::from_file(&keyring_path).unwrap();
+            std::fs::remove_file(&keyring_path).unwrap();
+            assert_eq!(
+                verify_pgp(&keyring, signature.as_bytes(), &path),
+                Err(reason.to_string())
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pin_fingerprints() {
+        let keyring_path = write_temp_file("openpgp", "keyring-pin.asc", PUBLIC_KEY.as_bytes());
+        let mut keyring = PgpKeyring::from_file(&keyring_path).unwrap();
+        std::fs::remove_file(&keyring_path).unwrap();
+
+        // gpg prints fingerprints in groups of four
+        assert!(keyring
+            .pin(&["9f26 2c6a 86a1 f369 b779  a37e 7c25 e55d 22d3 8d65".to_string()])
+            .is_ok());
+        assert!(keyring.pin(&["0".repeat(40)]).is_err());
+    }
+}
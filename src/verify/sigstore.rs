@@ -0,0 +1,1104 @@
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+use base64::Engine;
+use p256::pkcs8::DecodePublicKey;
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256, Sha384};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::models::{Asset, Release};
+
+// Verification follows the steps of the sigstore clients, with the following limitations:
+// - only ECDSA (P-256 and P-384) keys are supported, which is what Fulcio and Rekor use
+// - transparency log entries are checked through their signed entry timestamp,
+//   inclusion proofs and signed certificate timestamps are not verified
+// - RFC 3161 timestamps are not supported
+
+const OID_ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+const OID_ECDSA_WITH_SHA384: &str = "1.2.840.10045.4.3.3";
+// Fulcio certificate extensions containing the OIDC issuer of the identity,
+// the first one contains the raw string, the second one a DER encoded UTF8String
+const OID_FULCIO_ISSUER_V1: &str = "1.3.6.1.4.1.57264.1.1";
+const OID_FULCIO_ISSUER_V2: &str = "1.3.6.1.4.1.57264.1.8";
+
+// the maximum number of intermediate certificates between a leaf and a trust anchor
+const MAX_CHAIN_DEPTH: usize = 4;
+
+fn decode_base64(input: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::STANDARD
+        .decode(input.trim())
+        .map_err(|e| format!("invalid base64: {e}"))
+}
+
+// ===== json formats =====
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBytesJson {
+    raw_bytes: String,
+}
+
+#[derive(Deserialize)]
+struct CertificatesJson {
+    certificates: Vec<RawBytesJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyIdJson {
+    key_id: String,
+}
+
+// int64 values are encoded as strings in the protobuf json mapping,
+// but some producers write them as numbers
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Int64Json {
+    Number(i64),
+    String(String),
+}
+
+impl Int64Json {
+    fn value(&self) -> Result<i64, String> {
+        match self {
+            Int64Json::Number(number) => Ok(*number),
+            Int64Json::String(string) => string
+                .parse()
+                .map_err(|e| format!("invalid integer \"{string}\": {e}")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrustedRootJson {
+    #[serde(default)]
+    tlogs: Vec<TransparencyLogJson>,
+    #[serde(default)]
+    certificate_authorities: Vec<CertificateAuthorityJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransparencyLogJson {
+    public_key: RawBytesJson,
+    log_id: Option<KeyIdJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CertificateAuthorityJson {
+    cert_chain: CertificatesJson,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleJson {
+    verification_material: Option<VerificationMaterialJson>,
+    message_signature: Option<MessageSignatureJson>,
+    dsse_envelope: Option<DsseEnvelopeJson>,
+    // the following fields are used by the bundles of older cosign versions
+    base64_signature: Option<String>,
+    cert: Option<String>,
+    rekor_bundle: Option<CosignRekorBundleJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationMaterialJson {
+    certificate: Option<RawBytesJson>,
+    x509_certificate_chain: Option<CertificatesJson>,
+    #[serde(default)]
+    tlog_entries: Vec<TlogEntryJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TlogEntryJson {
+    log_index: Int64Json,
+    log_id: KeyIdJson,
+    integrated_time: Int64Json,
+    inclusion_promise: Option<InclusionPromiseJson>,
+    canonicalized_body: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InclusionPromiseJson {
+    signed_entry_timestamp: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageSignatureJson {
+    message_digest: Option<MessageDigestJson>,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct MessageDigestJson {
+    algorithm: String,
+    digest: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DsseEnvelopeJson {
+    payload: String,
+    payload_type: String,
+    signatures: Vec<DsseSignatureJson>,
+}
+
+#[derive(Deserialize)]
+struct DsseSignatureJson {
+    sig: String,
+}
+
+#[derive(Deserialize)]
+struct CosignRekorBundleJson {
+    #[serde(rename = "SignedEntryTimestamp")]
+    signed_entry_timestamp: String,
+    #[serde(rename = "Payload")]
+    payload: CosignRekorPayloadJson,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CosignRekorPayloadJson {
+    body: String,
+    integrated_time: i64,
+    log_index: i64,
+    #[serde(rename = "logID")]
+    log_id: String,
+}
+
+#[derive(Deserialize)]
+struct InTotoStatementJson {
+    subject: Vec<InTotoSubjectJson>,
+}
+
+#[derive(Deserialize)]
+struct InTotoSubjectJson {
+    digest: std::collections::HashMap<String, String>,
+}
+
+// ===== normalized bundle =====
+
+enum BundleContent {
+    MessageSignature {
+        digest: Option<Vec<u8>>,
+        signature: Vec<u8>,
+    },
+    Dsse {
+        payload_type: String,
+        payload: Vec<u8>,
+        signatures: Vec<Vec<u8>>,
+    },
+}
+
+struct TlogEntry {
+    log_id: Vec<u8>,
+    log_index: i64,
+    integrated_time: i64,
+    // base64 encoded, exactly as it has been signed
+    body: String,
+    signed_entry_timestamp: Option<Vec<u8>>,
+}
+
+struct Bundle {
+    // DER encoded, leaf first
+    certificates: Vec<Vec<u8>>,
+    content: BundleContent,
+    tlog_entries: Vec<TlogEntry>,
+}
+
+fn pem_to_der_certificates(pem: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let certificates: Vec<_> = x509_parser::pem::Pem::iter_from_buffer(pem)
+        .map(|pem| pem.map(|pem| pem.contents))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid PEM: {e}"))?;
+    if certificates.is_empty() {
+        return Err("no PEM encoded certificate found".to_string());
+    }
+    Ok(certificates)
+}
+
+impl Bundle {
+    fn parse(input: &[u8]) -> Result<Self, String> {
+        let json: BundleJson =
+            serde_json::from_slice(input).map_err(|e| format!("invalid sigstore bundle: {e}"))?;
+
+        if let (Some(signature), Some(cert)) = (&json.base64_signature, &json.cert) {
+            return Self::from_cosign_bundle(signature, cert, json.rekor_bundle);
+        }
+
+        let material = json
+            .verification_material
+            .ok_or("sigstore bundle does not contain verification material")?;
+        let certificates =
+            match (material.certificate, material.x509_certificate_chain) {
+                (Some(certificate), _) => vec![decode_base64(&certificate.raw_bytes)?],
+                (None, Some(chain)) => chain
+                    .certificates
+                    .iter()
+                    .map(|certificate| decode_base64(&certificate.raw_bytes))
+                    .collect::<Result<_, _>>()?,
+                (None, None) => return Err(
+                    "sigstore bundle does not contain a certificate, public keys are not supported"
+                        .to_string(),
+                ),
+            };
+
+        if certificates.is_empty() {
+            return Err("sigstore bundle does not contain a certificate".to_string());
+        }
+
+        let content = match (json.message_signature, json.dsse_envelope) {
+            (Some(message_signature), _) => {
+                let digest = match message_signature.message_digest {
+                    Some(digest) if digest.algorithm == "SHA2_256" => {
+                        Some(decode_base64(&digest.digest)?)
+                    }
+                    Some(digest) => {
+                        return Err(format!(
+                            "unsupported message digest algorithm \"{}\"",
+                            digest.algorithm
+                        ))
+                    }
+                    None => None,
+                };
+                BundleContent::MessageSignature {
+                    digest,
+                    signature: decode_base64(&message_signature.signature)?,
+                }
+            }
+            (None, Some(envelope)) => BundleContent::Dsse {
+                payload_type: envelope.payload_type,
+                payload: decode_base64(&envelope.payload)?,
+                signatures: envelope
+                    .signatures
+                    .iter()
+                    .map(|signature| decode_base64(&signature.sig))
+                    .collect::<Result<_, _>>()?,
+            },
+            (None, None) => {
+                return Err(
+                    "sigstore bundle contains neither a signature nor an envelope".to_string(),
+                )
+            }
+        };
+
+        let tlog_entries = material
+            .tlog_entries
+            .into_iter()
+            .map(|entry| {
+                Ok(TlogEntry {
+                    log_id: decode_base64(&entry.log_id.key_id)?,
+                    log_index: entry.log_index.value()?,
+                    integrated_time: entry.integrated_time.value()?,
+                    body: entry.canonicalized_body,
+                    signed_entry_timestamp: entry
+                        .inclusion_promise
+                        .map(|promise| decode_base64(&promise.signed_entry_timestamp))
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Bundle {
+            certificates,
+            content,
+            tlog_entries,
+        })
+    }
+
+    fn from_cosign_bundle(
+        signature: &str,
+        cert: &str,
+        rekor_bundle: Option<CosignRekorBundleJson>,
+    ) -> Result<Self, String> {
+        let certificates = pem_to_der_certificates(&decode_base64(cert)?)?;
+        let tlog_entries = match rekor_bundle {
+            Some(rekor_bundle) => vec![TlogEntry {
+                log_id: hex::decode(&rekor_bundle.payload.log_id)
+                    .map_err(|e| format!("invalid log id: {e}"))?,
+                log_index: rekor_bundle.payload.log_index,
+                integrated_time: rekor_bundle.payload.integrated_time,
+                body: rekor_bundle.payload.body,
+                signed_entry_timestamp: Some(decode_base64(&rekor_bundle.signed_entry_timestamp)?),
+            }],
+            None => vec![],
+        };
+        Ok(Bundle {
+            certificates,
+            content: BundleContent::MessageSignature {
+                digest: None,
+                signature: decode_base64(signature)?,
+            },
+            tlog_entries,
+        })
+    }
+}
+
+// ===== keys =====
+
+enum EcdsaKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+}
+
+impl EcdsaKey {
+    fn from_spki_der(der: &[u8]) -> Result<Self, String> {
+        if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(der) {
+            return Ok(EcdsaKey::P256(key));
+        }
+        if let Ok(key) = p384::ecdsa::VerifyingKey::from_public_key_der(der) {
+            return Ok(EcdsaKey::P384(key));
+        }
+        Err("unsupported public key, only ECDSA P-256 and P-384 keys are supported".to_string())
+    }
+
+    // the hash algorithm sigstore clients use with the curve
+    fn hash(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            EcdsaKey::P256(_) => Sha256::digest(message).to_vec(),
+            EcdsaKey::P384(_) => Sha384::digest(message).to_vec(),
+        }
+    }
+
+    fn verify_prehash(&self, prehash: &[u8], der_signature: &[u8]) -> Result<(), String> {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+        let result = match self {
+            EcdsaKey::P256(key) => p256::ecdsa::Signature::from_der(der_signature)
+                .and_then(|signature| key.verify_prehash(prehash, &signature)),
+            EcdsaKey::P384(key) => p384::ecdsa::Signature::from_der(der_signature)
+                .and_then(|signature| key.verify_prehash(prehash, &signature)),
+        };
+        result.map_err(|_| "invalid signature".to_string())
+    }
+
+    fn verify(&self, message: &[u8], der_signature: &[u8]) -> Result<(), String> {
+        self.verify_prehash(&self.hash(message), der_signature)
+    }
+}
+
+fn parse_certificate(der: &[u8]) -> Result<X509Certificate<'_>, String> {
+    X509Certificate::from_der(der)
+        .map(|(_, certificate)| certificate)
+        .map_err(|e| format!("invalid certificate: {e}"))
+}
+
+fn verify_certificate_signature(
+    certificate: &X509Certificate,
+    issuer: &X509Certificate,
+) -> Result<(), String> {
+    let tbs_certificate = certificate.tbs_certificate.as_ref();
+    let prehash = match certificate
+        .signature_algorithm
+        .algorithm
+        .to_id_string()
+        .as_str()
+    {
+        OID_ECDSA_WITH_SHA256 => Sha256::digest(tbs_certificate).to_vec(),
+        OID_ECDSA_WITH_SHA384 => Sha384::digest(tbs_certificate).to_vec(),
+        oid => return Err(format!("unsupported certificate signature algorithm {oid}")),
+    };
+    let issuer_key = EcdsaKey::from_spki_der(issuer.public_key().raw)?;
+    issuer_key.verify_prehash(&prehash, &certificate.signature_value.data)
+}
+
+// whether the pathLenConstraint of a certificate authority allows
+// `intermediates_below` intermediate certificates below it
+fn path_length_allows(certificate: &X509Certificate, intermediates_below: usize) -> bool {
+    match certificate.basic_constraints() {
+        Ok(Some(basic_constraints)) => basic_constraints
+            .value
+            .path_len_constraint
+            .is_none_or(|path_length| intermediates_below <= path_length as usize),
+        Ok(None) => true,
+        Err(_) => false,
+    }
+}
+
+fn is_valid_at(certificate: &X509Certificate, time: i64) -> bool {
+    let validity = certificate.validity();
+    validity.not_before.timestamp() <= time && time <= validity.not_after.timestamp()
+}
+
+// ===== trust root =====
+
+struct TransparencyLogKey {
+    log_id: Vec<u8>,
+    key: Vec<u8>,
+}
+
+/// Certificate authorities and transparency logs sigstore bundles are verified against.
+pub struct SigstoreTrustRoot {
+    // DER encoded
+    certificate_authorities: Vec<Vec<u8>>,
+    transparency_logs: Vec<TransparencyLogKey>,
+}
+
+impl std::fmt::Debug for SigstoreTrustRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigstoreTrustRoot")
+            .field(
+                "certificate_authorities",
+                &self.certificate_authorities.len(),
+            )
+            .field("transparency_logs", &self.transparency_logs.len())
+            .finish()
+    }
+}
+
+impl SigstoreTrustRoot {
+    /// Reads a sigstore `trusted_root.json`.
+    ///
+    /// Bundles can only be verified with a trust root containing a transparency log,
+    /// since it establishes the time of signing.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read(path)
+            .map_err(|e| format!("could not read trust root \"{}\": {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| format!("invalid trust root \"{}\": {e}", path.display()))
+    }
+
+    fn parse(content: &[u8]) -> Result<Self, String> {
+        if content.trim_ascii_start().starts_with(b"-----BEGIN") {
+            return Err(
+                "PEM files do not contain the transparency log needed to establish the time of signing, use a trusted_root.json"
+                    .to_string(),
+            );
+        }
+
+        let json: TrustedRootJson = serde_json::from_slice(content).map_err(|e| e.to_string())?;
+        let certificate_authorities: Vec<_> = json
+            .certificate_authorities
+            .iter()
+            .flat_map(|authority| &authority.cert_chain.certificates)
+            .map(|certificate| decode_base64(&certificate.raw_bytes))
+            .collect::<Result<_, _>>()?;
+        if certificate_authorities.is_empty() {
+            return Err("no certificate authority found".to_string());
+        }
+        let transparency_logs = json
+            .tlogs
+            .iter()
+            .map(|log| {
+                let key = decode_base64(&log.public_key.raw_bytes)?;
+                // the log id is the sha256 of the public key
+                let log_id = match &log.log_id {
+                    Some(log_id) => decode_base64(&log_id.key_id)?,
+                    None => Sha256::digest(&key).to_vec(),
+                };
+                Ok(TransparencyLogKey { log_id, key })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            certificate_authorities,
+            transparency_logs,
+        })
+    }
+}
+
+// ===== verification =====
+
+/// Verifies sigstore bundles with a trust root and an optional identity policy.
+#[derive(Debug)]
+pub struct SigstoreVerifier {
+    pub trust_root: SigstoreTrustRoot,
+    /// Regex the identity (SAN) of the signing certificate must match completely.
+    pub identity: Option<Regex>,
+    /// OIDC issuer of the identity, e.g. "https://token.actions.githubusercontent.com".
+    pub issuer: Option<String>,
+    /// Regex for the bundle asset, if it can not be found by the asset's name.
+    pub bundle_pattern: Option<Regex>,
+}
+
+/// Finds the sigstore bundle of `asset` in `release`.
+///
+/// If `pattern` is passed, the first other asset matching it is used. Otherwise
+/// "<asset>.sigstore.json", "<asset>.sigstore" and "<asset>.bundle" are looked for.
+pub fn find_sigstore_bundle_asset<'a>(
+    release: &'a Release,
+    asset: &Asset,
+    pattern: Option<&Regex>,
+) -> Option<&'a Asset> {
+    if let Some(pattern) = pattern {
+        return release
+            .assets
+            .iter()
+            .find(|candidate| candidate.name != asset.name && pattern.is_match(&candidate.name));
+    }
+    [".sigstore.json", ".sigstore", ".bundle"]
+        .iter()
+        .find_map(|extension| {
+            let bundle_name = format!("{}{extension}", asset.name);
+            release
+                .assets
+                .iter()
+                .find(|candidate| candidate.name == bundle_name)
+        })
+}
+
+fn hash_file<D: sha2::Digest>(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = D::new();
+    let mut buffer = [0_u8; 8192];
+    loop {
+        let read_size = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read_size == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read_size]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+// pre-authentication encoding of DSSE, which is what is actually signed
+fn dsse_pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut pae = format!(
+        "DSSEv1 {} {payload_type} {} ",
+        payload_type.len(),
+        payload.len()
+    )
+    .into_bytes();
+    pae.extend_from_slice(payload);
+    pae
+}
+
+fn der_utf8_string(der: &[u8]) -> Option<String> {
+    // tag 0x0c (UTF8String) followed by the length in short or long form
+    let (&tag, rest) = der.split_first()?;
+    let (&length, rest) = rest.split_first()?;
+    if tag != 0x0c {
+        return None;
+    }
+    let (length, rest) = if length & 0x80 == 0 {
+        (length as usize, rest)
+    } else {
+        let length_size = (length & 0x7f) as usize;
+        if length_size > std::mem::size_of::<usize>() || rest.len() < length_size {
+            return None;
+        }
+        let (length_bytes, rest) = rest.split_at(length_size);
+        let length = length_bytes
+            .iter()
+            .fold(0_usize, |length, &byte| (length << 8) | byte as usize);
+        (length, rest)
+    };
+    String::from_utf8(rest.get(..length)?.to_vec()).ok()
+}
+
+fn certificate_identities(certificate: &X509Certificate) -> Vec<String> {
+    let Ok(Some(san)) = certificate.subject_alternative_name() else {
+        return vec![];
+    };
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::URI(uri) => Some(uri.to_string()),
+            GeneralName::RFC822Name(email) => Some(email.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn certificate_issuer(certificate: &X509Certificate) -> Option<String> {
+    let extensions = certificate.extensions();
+    let find = |oid: &str| {
+        extensions
+            .iter()
+            .find(|extension| extension.oid.to_id_string() == oid)
+    };
+    if let Some(extension) = find(OID_FULCIO_ISSUER_V2) {
+        return der_utf8_string(extension.value);
+    }
+    find(OID_FULCIO_ISSUER_V1)
+        .and_then(|extension| String::from_utf8(extension.value.to_vec()).ok())
+}
+
+// the key or certificate of a transparency log entry, a base64 encoded PEM,
+// has to be the leaf certificate of the bundle or its public key
+fn is_logged_verifier(logged: Option<&str>, leaf_der: &[u8]) -> Result<bool, String> {
+    let Some(logged) = logged else {
+        return Ok(false);
+    };
+    let pem = decode_base64(logged)?;
+    let Some(pem) = x509_parser::pem::Pem::iter_from_buffer(&pem).next() else {
+        return Ok(false);
+    };
+    let pem = pem.map_err(|e| format!("invalid PEM in transparency log entry: {e}"))?;
+    Ok(match pem.label.as_str() {
+        "CERTIFICATE" => pem.contents == leaf_der,
+        "PUBLIC KEY" => pem.contents == parse_certificate(leaf_der)?.public_key().raw,
+        _ => false,
+    })
+}
+
+// intoto entries store the signatures of the envelope base64 encoded once more
+fn is_logged_signature(logged: Option<&str>, signature: &[u8]) -> bool {
+    let Some(logged) = logged.and_then(|logged| decode_base64(logged).ok()) else {
+        return false;
+    };
+    logged == signature
+        || std::str::from_utf8(&logged)
+            .ok()
+            .and_then(|logged| decode_base64(logged).ok())
+            .is_some_and(|logged| logged == signature)
+}
+
+// checks that the body of a transparency log entry is about the verified
+// signature, made with the key of the leaf certificate of the bundle
+fn check_tlog_body(
+    body: &serde_json::Value,
+    bundle: &Bundle,
+    signature: &[u8],
+    signed_digest: &[u8],
+) -> Result<(), String> {
+    let leaf_der = &bundle.certificates[0];
+    let spec = &body["spec"];
+    match (body["kind"].as_str(), &bundle.content) {
+        (Some("hashedrekord"), BundleContent::MessageSignature { .. }) => {
+            let logged_digest = spec["data"]["hash"]["value"].as_str();
+            let logged_signature = spec["signature"]["content"]
+                .as_str()
+                .map(decode_base64)
+                .transpose()?;
+            if logged_digest != Some(hex::encode(signed_digest).as_str())
+                || logged_signature.as_deref() != Some(signature)
+            {
+                return Err("transparency log entry is about a different signature".to_string());
+            }
+            if !is_logged_verifier(spec["signature"]["publicKey"]["content"].as_str(), leaf_der)? {
+                return Err("transparency log entry is about a different certificate".to_string());
+            }
+        }
+        (
+            Some("dsse") | Some("intoto"),
+            BundleContent::Dsse {
+                payload_type,
+                payload,
+                ..
+            },
+        ) => {
+            let payload_hex = hex::encode(Sha256::digest(payload));
+            let logged_payload_hash = spec["payloadHash"]["value"]
+                .as_str()
+                .or_else(|| spec["content"]["payloadHash"]["value"].as_str());
+            if logged_payload_hash != Some(payload_hex.as_str()) {
+                return Err("transparency log entry is about a different envelope".to_string());
+            }
+            // dsse entries do not log the payload type, it is covered by the signature
+            let envelope = &spec["content"]["envelope"];
+            if let Some(logged_payload_type) = envelope["payloadType"].as_str() {
+                if logged_payload_type != payload_type {
+                    return Err(
+                        "transparency log entry is about a different payload type".to_string()
+                    );
+                }
+            }
+            let logged_signatures = spec["signatures"]
+                .as_array()
+                .or_else(|| envelope["signatures"].as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut is_logged = false;
+            for logged in logged_signatures {
+                let logged_verifier = logged["verifier"]
+                    .as_str()
+                    .or_else(|| logged["publicKey"].as_str());
+                let logged_signature = logged["signature"]
+                    .as_str()
+                    .or_else(|| logged["sig"].as_str());
+                if is_logged_signature(logged_signature, signature)
+                    && is_logged_verifier(logged_verifier, leaf_der)?
+                {
+                    is_logged = true;
+                    break;
+                }
+            }
+            if !is_logged {
+                return Err(
+                    "transparency log entry does not contain the signature of the envelope"
+                        .to_string(),
+                );
+            }
+        }
+        (kind, _) => {
+            return Err(format!(
+                "unsupported transparency log entry kind {}",
+                kind.unwrap_or("unknown")
+            ))
+        }
+    }
+    Ok(())
+}
+
+impl SigstoreVerifier {
+    /// Verifies the file at `path` against a sigstore bundle.
+    ///
+    /// Returns the identity of the signing certificate.
+    pub fn verify(&self, bundle: &[u8], path: &Path) -> Result<String, String> {
+        let bundle = Bundle::parse(bundle)?;
+        let artifact_digest = hash_file::<Sha256>(path)?;
+
+        let leaf = parse_certificate(&bundle.certificates[0])?;
+        let leaf_key = EcdsaKey::from_spki_der(leaf.public_key().raw)?;
+
+        // the signature must cover the downloaded file,
+        // signed_digest is the digest that has been signed (and logged) for message signatures
+        let (signature, signed_digest) = match &bundle.content {
+            BundleContent::MessageSignature { digest, signature } => {
+                if digest
+                    .as_ref()
+                    .is_some_and(|digest| digest != &artifact_digest)
+                {
+                    return Err("bundle has been made for a different file".to_string());
+                }
+                let signed_digest = match leaf_key {
+                    EcdsaKey::P256(_) => artifact_digest.clone(),
+                    EcdsaKey::P384(_) => hash_file::<Sha384>(path)?,
+                };
+                leaf_key.verify_prehash(&signed_digest, signature)?;
+                (signature.clone(), signed_digest)
+            }
+            BundleContent::Dsse {
+                payload_type,
+                payload,
+                signatures,
+            } => {
+                let pae = dsse_pae(payload_type, payload);
+                let signature = signatures
+                    .iter()
+                    .find(|signature| leaf_key.verify(&pae, signature).is_ok())
+                    .ok_or("no valid signature in DSSE envelope")?;
+                let statement: InTotoStatementJson = serde_json::from_slice(payload)
+                    .map_err(|e| format!("invalid in-toto statement: {e}"))?;
+                let artifact_hex = hex::encode(&artifact_digest);
+                let covers_artifact = statement.subject.iter().any(|subject| {
+                    subject
+                        .digest
+                        .get("sha256")
+                        .is_some_and(|digest| digest.eq_ignore_ascii_case(&artifact_hex))
+                });
+                if !covers_artifact {
+                    return Err("attestation does not list the file as a subject".to_string());
+                }
+                (signature.clone(), artifact_digest)
+            }
+        };
+
+        let signing_time = self.verify_signing_time(&bundle, &signature, &signed_digest)?;
+        self.verify_certificate_chain(&bundle.certificates, signing_time)?;
+
+        let identities = certificate_identities(&leaf);
+        if let Some(expected_issuer) = &self.issuer {
+            let issuer = certificate_issuer(&leaf);
+            if issuer.as_deref() != Some(expected_issuer.as_str()) {
+                return Err(format!(
+                    "certificate has been issued for an identity of \"{}\", expected \"{expected_issuer}\"",
+                    issuer.as_deref().unwrap_or("unknown issuer")
+                ));
+            }
+        }
+        if let Some(expected_identity) = &self.identity {
+            // anchor the whole pattern, so every branch of an alternation
+            // has to match the identity completely
+            let anchored_identity = Regex::new(&format!("^(?:{})$", expected_identity.as_str()))
+                .map_err(|e| format!("invalid identity pattern: {e}"))?;
+            if !identities
+                .iter()
+                .any(|identity| anchored_identity.is_match(identity))
+            {
+                return Err(format!(
+                    "certificate identity {identities:?} does not match \"{expected_identity}\""
+                ));
+            }
+        }
+
+        Ok(identities
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| "unknown identity".to_string()))
+    }
+
+    // returns the time the signature has been made at, as established by the transparency log
+    fn verify_signing_time(
+        &self,
+        bundle: &Bundle,
+        signature: &[u8],
+        signed_digest: &[u8],
+    ) -> Result<i64, String> {
+        if self.trust_root.transparency_logs.is_empty() {
+            // without transparency logs, there is nothing that attests the time of signing,
+            // and the short-lived signing certificates can not be checked against it
+            return Err("trust root does not contain a transparency log".to_string());
+        }
+
+        let mut last_error = "bundle does not contain a transparency log entry".to_string();
+        for entry in &bundle.tlog_entries {
+            match self.verify_tlog_entry(entry, bundle, signature, signed_digest) {
+                Ok(()) => return Ok(entry.integrated_time),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn verify_tlog_entry(
+        &self,
+        entry: &TlogEntry,
+        bundle: &Bundle,
+        signature: &[u8],
+        signed_digest: &[u8],
+    ) -> Result<(), String> {
+        let log = self
+            .trust_root
+            .transparency_logs
+            .iter()
+            .find(|log| log.log_id == entry.log_id)
+            .ok_or("transparency log entry is from an unknown log")?;
+        let signed_entry_timestamp = entry
+            .signed_entry_timestamp
+            .as_ref()
+            .ok_or("transparency log entry has no signed entry timestamp")?;
+
+        // the signed entry timestamp is made over the canonical json (sorted keys, no whitespace)
+        let canonical_entry = format!(
+            r#"{{"body":"{}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+            entry.body,
+            entry.integrated_time,
+            hex::encode(&entry.log_id),
+            entry.log_index
+        );
+        EcdsaKey::from_spki_der(&log.key)?
+            .verify(canonical_entry.as_bytes(), signed_entry_timestamp)
+            .map_err(|_| "invalid signed entry timestamp".to_string())?;
+
+        // the entry must be about this signature
+        let body: serde_json::Value = serde_json::from_slice(&decode_base64(&entry.body)?)
+            .map_err(|e| format!("invalid transparency log entry: {e}"))?;
+        check_tlog_body(&body, bundle, signature, signed_digest)
+    }
+
+    fn verify_certificate_chain(&self, certificates: &[Vec<u8>], time: i64) -> Result<(), String> {
+        let anchors: Vec<_> = self
+            .trust_root
+            .certificate_authorities
+            .iter()
+            .map(|der| parse_certificate(der))
+            .collect::<Result<_, _>>()?;
+        let intermediates: Vec<_> = certificates[1..]
+            .iter()
+            .map(|der| parse_certificate(der))
+            .collect::<Result<_, _>>()?;
+
+        let leaf = parse_certificate(&certificates[0])?;
+        if leaf.is_ca() {
+            return Err("signing certificate is a certificate authority".to_string());
+        }
+        let is_code_signing = leaf
+            .extended_key_usage()
+            .ok()
+            .flatten()
+            .is_some_and(|extended_key_usage| extended_key_usage.value.code_signing);
+        if !is_code_signing {
+            return Err("signing certificate is not valid for code signing".to_string());
+        }
+
+        let mut current = leaf;
+        // the number of intermediates below the issuer of `current`
+        for intermediates_below in 0..=MAX_CHAIN_DEPTH {
+            if !is_valid_at(&current, time) {
+                return Err(format!(
+                    "certificate \"{}\" was not valid at the time of signing",
+                    current.subject()
+                ));
+            }
+            let is_signed_by = |issuer: &&X509Certificate| {
+                issuer.is_ca()
+                    && path_length_allows(issuer, intermediates_below)
+                    && issuer.subject() == current.issuer()
+                    && verify_certificate_signature(&current, issuer).is_ok()
+            };
+            if let Some(anchor) = anchors.iter().find(is_signed_by) {
+                if !is_valid_at(anchor, time) {
+                    return Err(format!(
+                        "certificate authority \"{}\" was not valid at the time of signing",
+                        anchor.subject()
+                    ));
+                }
+                return Ok(());
+            }
+            match intermediates.iter().find(is_signed_by) {
+                Some(intermediate) => current = intermediate.clone(),
+                None => break,
+            }
+        }
+        Err("certificate has not been issued by a trusted certificate authority".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_temp_file;
+
+    // the fixtures use a test CA and transparency log,
+    // the signatures have been made over "test"
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sigstore");
+    const IDENTITY: &str =
+        "https://github.com/owner/repo/.github/workflows/release.yml@refs/tags/v1.0.0";
+    const ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+    fn verifier(identity: Option<&str>, issuer: Option<&str>) -> SigstoreVerifier {
+        SigstoreVerifier {
+            trust_root: SigstoreTrustRoot::from_file(
+                &Path::new(FIXTURES).join("trusted_root.json"),
+            )
+            .unwrap(),
+            identity: identity.map(|identity| Regex::new(identity).unwrap()),
+            issuer: issuer.map(ToString::to_string),
+            bundle_pattern: None,
+        }
+    }
+
+    fn read_fixture(name: &str) -> Vec<u8> {
+        fs::read(Path::new(FIXTURES).join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_verify_message_signature_bundle() {
+        let bundle = read_fixture("test.sigstore.json");
+        let path = write_temp_file("sigstore", "message", b"test");
+        assert_eq!(
+            verifier(Some(IDENTITY), Some(ISSUER)).verify(&bundle, &path),
+            Ok(IDENTITY.to_string())
+        );
+        fs::write(&path, b"Test").unwrap();
+        assert!(verifier(None, None).verify(&bundle, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_dsse_bundle() {
+        let bundle = read_fixture("test.intoto.sigstore.json");
+        let path = write_temp_file("sigstore", "dsse", b"test");
+        assert!(verifier(Some(IDENTITY), Some(ISSUER))
+            .verify(&bundle, &path)
+            .is_ok());
+        fs::write(&path, b"Test").unwrap();
+        assert!(verifier(None, None).verify(&bundle, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_identity_policy() {
+        let bundle = read_fixture("test.sigstore.json");
+        let path = write_temp_file("sigstore", "identity", b"test");
+        let workflow_regex = r"https://github\.com/owner/repo/\.github/workflows/.*";
+        assert!(verifier(Some(workflow_regex), None)
+            .verify(&bundle, &path)
+            .is_ok());
+        // the identity must match completely
+        assert!(verifier(Some("https://github.com/owner"), None)
+            .verify(&bundle, &path)
+            .is_err());
+        assert!(verifier(None, Some("https://accounts.google.com"))
+            .verify(&bundle, &path)
+            .is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_identity_alternation() {
+        let bundle = read_fixture("test.sigstore.json");
+        let path = write_temp_file("sigstore", "alternation", b"test");
+        // the leftmost branch matches a prefix only, the second one the whole identity
+        let alternation = format!("https://github\\.com/owner|{}", regex::escape(IDENTITY));
+        assert!(verifier(Some(&alternation), None)
+            .verify(&bundle, &path)
+            .is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_signing_certificate_constraints() {
+        let path = write_temp_file("sigstore", "constraints", b"test");
+        for fixture in ["no_code_signing.sigstore.json", "path_length.sigstore.json"] {
+            let bundle = read_fixture(fixture);
+            assert!(verifier(None, None).verify(&bundle, &path).is_err());
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_untrusted_certificate_authority() {
+        // same subject as the CA of the fixtures, but a different key
+        let mut verifier = verifier(None, None);
+        verifier.trust_root.certificate_authorities =
+            pem_to_der_certificates(&read_fixture("other_root.pem")).unwrap();
+        let bundle = read_fixture("test.sigstore.json");
+        let path = write_temp_file("sigstore", "untrusted", b"test");
+        assert!(verifier.verify(&bundle, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trust_root_without_transparency_log() {
+        assert!(SigstoreTrustRoot::parse(&read_fixture("other_root.pem")).is_err());
+
+        let mut verifier = verifier(None, None);
+        verifier.trust_root.transparency_logs.clear();
+        let bundle = read_fixture("test.sigstore.json");
+        let path = write_temp_file("sigstore", "no-tlog", b"test");
+        assert!(verifier.verify(&bundle, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tlog_body_binds_certificate_and_signature() {
+        let other_certificate =
+            base64::engine::general_purpose::STANDARD.encode(read_fixture("other_root.pem"));
+        let signed_digest = Sha256::digest(b"test").to_vec();
+        let check = |bundle: &Bundle, body: &serde_json::Value| {
+            let signature = match &bundle.content {
+                BundleContent::MessageSignature { signature, .. } => signature,
+                BundleContent::Dsse { signatures, .. } => &signatures[0],
+            };
+            check_tlog_body(body, bundle, signature, &signed_digest)
+        };
+        let body = |bundle: &Bundle| -> serde_json::Value {
+            serde_json::from_slice(&decode_base64(&bundle.tlog_entries[0].body).unwrap()).unwrap()
+        };
+
+        let bundle = Bundle::parse(&read_fixture("test.sigstore.json")).unwrap();
+        let mut logged = body(&bundle);
+        assert_eq!(check(&bundle, &logged), Ok(()));
+        logged["spec"]["signature"]["publicKey"]["content"] = other_certificate.clone().into();
+        assert!(check(&bundle, &logged).is_err());
+
+        let bundle = Bundle::parse(&read_fixture("test.intoto.sigstore.json")).unwrap();
+        let mut logged = body(&bundle);
+        assert_eq!(check(&bundle, &logged), Ok(()));
+        logged["spec"]["signatures"][0]["verifier"] = other_certificate.into();
+        assert!(check(&bundle, &logged).is_err());
+        let mut logged = body(&bundle);
+        logged["spec"]["signatures"][0]["signature"] = "MEUCIQ==".into();
+        assert!(check(&bundle, &logged).is_err());
+    }
+
+    #[test]
+    fn test_der_utf8_string() {
+        assert_eq!(der_utf8_string(b"\x0c\x03abc").as_deref(), Some("abc"));
+        assert_eq!(der_utf8_string(b"\x0c\x81\x03abc").as_deref(), Some("abc"));
+        assert_eq!(der_utf8_string(b"\x04\x03abc"), None);
+    }
+}
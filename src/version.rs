@@ -0,0 +1,182 @@
+//! Selecting releases by semantic version requirements like `>=1.4, <2`,
+//! `~1.4` or `^1.4`, matched against the versions in the release tags.
+
+pub use semver::{Version, VersionReq};
+
+use regex::Regex;
+
+use crate::{models::Release, Error};
+
+/// Parses a requirement in the syntax of Cargo, e.g. `>=1.4, <2`, `~1.4` or `^1.4`.
+pub fn parse_requirement(requirement: &str) -> Result<VersionReq, Error> {
+    VersionReq::parse(requirement).map_err(|e| {
+        Error::InvalidArgument(format!(
+            "\"{requirement}\" is not a valid version requirement: {e}"
+        ))
+    })
+}
+
+/// Parses the version of a tag like "v1.4.2", or "release-1.4.2" with the
+/// tag prefix "release-", `None` if the tag is not a semantic version.
+///
+/// A "v" in front of the version is always accepted and missing minor and
+/// patch versions are filled in, so "v1.4" is version 1.4.0.
+pub fn parse_tag_version(tag: &str, tag_prefix: Option<&str>) -> Option<Version> {
+    let version = match tag_prefix {
+        Some(tag_prefix) => tag.strip_prefix(tag_prefix)?,
+        None => tag,
+    };
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    if let Ok(version) = Version::parse(version) {
+        return Some(version);
+    }
+    let core_end = version.find(['-', '+']).unwrap_or(version.len());
+    let (core, rest) = version.split_at(core_end);
+    let components = core.split('.').count();
+    if components >= 3 {
+        return None;
+    }
+    Version::parse(&format!("{core}{}{rest}", ".0".repeat(3 - components))).ok()
+}
+
+/// The part of `tag` holding its version: the first capture group of
+/// `tag_pattern`, or the whole tag if there is no pattern or it has no group.
+/// `None` if the tag does not match the pattern.
+pub fn version_text<'t>(tag: &'t str, tag_pattern: Option<&Regex>) -> Option<&'t str> {
+    let Some(tag_pattern) = tag_pattern else {
+        return Some(tag);
+    };
+    let captures = tag_pattern.captures(tag)?;
+    Some(captures.get(1).map_or(tag, |version| version.as_str()))
+}
+
+/// The version of `tag` if it matches `requirement`, see [`version_text`] for
+/// `tag_pattern` and [`parse_tag_version`] for `tag_prefix`.
+///
+/// Versions with a pre-release part (like "2.0.0-rc.1") only match
+/// requirements which name a pre-release of the same version, unless
+/// `allow_prerelease` is set, then they match if their version without the
+/// pre-release part does.
+pub fn matching_version(
+    tag: &str,
+    requirement: &VersionReq,
+    tag_pattern: Option<&Regex>,
+    tag_prefix: Option<&str>,
+    allow_prerelease: bool,
+) -> Option<Version> {
+    let version = parse_tag_version(version_text(tag, tag_pattern)?, tag_prefix)?;
+    let matches = requirement.matches(&version)
+        || (allow_prerelease
+            && !version.pre.is_empty()
+            && requirement.matches(&Version::new(version.major, version.minor, version.patch)));
+    matches.then_some(version)
+}
+
+/// Returns the release with the highest version matching `requirement`, tags
+/// which are not semantic versions are ignored.
+///
+/// Drafts are skipped, prereleases unless `allow_prerelease` is set.
+pub fn find_release_by_version<'a>(
+    releases: &'a [Release],
+    requirement: &VersionReq,
+    tag_pattern: Option<&Regex>,
+    tag_prefix: Option<&str>,
+    allow_prerelease: bool,
+) -> Option<&'a Release> {
+    releases
+        .iter()
+        // of releases with the same version the newest one is used
+        .rev()
+        .filter(|release| !release.draft && (!release.prerelease || allow_prerelease))
+        .filter_map(|release| {
+            let version = matching_version(
+                &release.tag_name,
+                requirement,
+                tag_pattern,
+                tag_prefix,
+                allow_prerelease,
+            )?;
+            Some((version, release))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::release;
+
+    #[test]
+    fn test_parse_tag_version() {
+        let version = |major, minor, patch| Some(Version::new(major, minor, patch));
+        assert_eq!(parse_tag_version("1.4.2", None), version(1, 4, 2));
+        assert_eq!(parse_tag_version("v1.4.2", None), version(1, 4, 2));
+        assert_eq!(parse_tag_version("v1.4", None), version(1, 4, 0));
+        assert_eq!(parse_tag_version("V2", None), version(2, 0, 0));
+        assert_eq!(
+            parse_tag_version("release-v1.4.2", Some("release-")),
+            version(1, 4, 2)
+        );
+        assert_eq!(
+            parse_tag_version("v2.0-rc.1", None),
+            Some(Version::parse("2.0.0-rc.1").unwrap())
+        );
+        assert_eq!(parse_tag_version("release-1.4.2", None), None);
+        assert_eq!(parse_tag_version("1.4.2", Some("release-")), None);
+        assert_eq!(parse_tag_version("nightly", None), None);
+        assert_eq!(parse_tag_version("1.2.3.4", None), None);
+    }
+
+    #[test]
+    fn test_find_release_by_version() {
+        let releases = vec![
+            release("v2.1.0", false, &[]),
+            release("nightly", false, &[]),
+            release("v1.10.0", false, &[]),
+            release("v1.5.0-rc.1", true, &[]),
+            release("v1.4.3", false, &[]),
+            release("v1.3.0", false, &[]),
+        ];
+        let find = |requirement: &str, allow_prerelease| {
+            let requirement = parse_requirement(requirement).unwrap();
+            find_release_by_version(&releases, &requirement, None, None, allow_prerelease)
+                .map(|release| release.tag_name.as_str())
+        };
+        assert_eq!(find(">=1.4, <2", false), Some("v1.10.0"));
+        assert_eq!(find("~1.4", false), Some("v1.4.3"));
+        assert_eq!(find("^1.4", false), Some("v1.10.0"));
+        assert_eq!(find("~1.5", false), None);
+        assert_eq!(find("~1.5", true), Some("v1.5.0-rc.1"));
+        assert_eq!(find("*", false), Some("v2.1.0"));
+        assert_eq!(find(">=3", true), None);
+        assert!(parse_requirement("1.4 or 2").is_err());
+    }
+
+    #[test]
+    fn test_find_release_by_version_with_tag_pattern() {
+        let releases = vec![
+            release("server-v3.1.0", false, &[]),
+            release("cli-v1.2.0", false, &[]),
+            release("cli-v1.10.0", false, &[]),
+            release("cli-nightly", false, &[]),
+        ];
+        let tag_pattern = Regex::new("^cli-(.+)$").unwrap();
+        let requirement = parse_requirement("^1").unwrap();
+        let found =
+            find_release_by_version(&releases, &requirement, Some(&tag_pattern), None, false);
+        assert_eq!(found.unwrap().tag_name, "cli-v1.10.0");
+
+        // without a group the whole tag has to be a version
+        let tag_pattern = Regex::new("^cli-").unwrap();
+        assert!(
+            find_release_by_version(&releases, &requirement, Some(&tag_pattern), None, false)
+                .is_none()
+        );
+        assert_eq!(version_text("server-v3.1.0", Some(&tag_pattern)), None);
+        assert_eq!(
+            version_text("cli-v1.2.0", Some(&tag_pattern)),
+            Some("cli-v1.2.0")
+        );
+    }
+}
@@ -41,3 +41,463 @@ fn argument_dash_v_prints_version_from_cargo_toml() -> Result<(), Box<dyn std::e
 
     Ok(())
 }
+
+// the following errors are detected before any request is sent,
+// so they can be tested without network access
+
+#[test]
+fn invalid_header_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "--header",
+        "no-colon-here",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("no-colon-here"));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_asset_pattern_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "(unclosed",
+    ]);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_include_glob_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "linux",
+        "--extract",
+        "--include",
+        "bin/[unclosed",
+    ]);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn stream_with_signature_fails_code_2() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "linux",
+        "--extract",
+        "--stream",
+        "--minisign-key",
+        "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3",
+    ]);
+
+    cmd.assert().failure().code(2);
+
+    Ok(())
+}
+
+#[test]
+fn passed_tag_replaces_version_from_env() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "(unclosed",
+        "--tag",
+        "v1",
+    ])
+    .env("GRD_VERSION", "^1");
+
+    // not the conflict of the arguments, but the invalid asset pattern fails
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn passed_signature_replaces_stream_from_env() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "(unclosed",
+        "--extract",
+        "--minisign-key",
+        "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3",
+    ])
+    .env("GRD_STREAM", "true");
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn install_with_invalid_name_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "install",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "linux",
+        "--bin-dir",
+        "bin",
+        "--name",
+        "../grd",
+    ]);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn list_without_receipts_prints_empty_json() -> Result<(), Box<dyn std::error::Error>> {
+    let state_dir = std::env::temp_dir().join(format!("grd-cli-{}-list", std::process::id()));
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["list", "--json", "--state-dir"]).arg(&state_dir);
+
+    cmd.assert().success().stdout("[]\n");
+
+    Ok(())
+}
+
+#[test]
+fn update_of_unknown_tool_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let state_dir = std::env::temp_dir().join(format!("grd-cli-{}-update", std::process::id()));
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["update", "unknown-tool", "--state-dir"])
+        .arg(&state_dir);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn rollback_of_unknown_tool_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let state_dir = std::env::temp_dir().join(format!("grd-cli-{}-rollback", std::process::id()));
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["rollback", "unknown-tool", "--state-dir"])
+        .arg(&state_dir);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn gc_without_policy_fails_code_2() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["gc", "--dry-run"]);
+
+    cmd.assert().failure().code(2);
+
+    Ok(())
+}
+
+#[test]
+fn uninstall_of_unknown_tool_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let state_dir = std::env::temp_dir().join(format!("grd-cli-{}-uninstall", std::process::id()));
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["uninstall", "unknown-tool", "--state-dir"])
+        .arg(&state_dir);
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn sync_without_manifest_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-sync-missing", std::process::id()));
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["sync", "--dry-run", "--manifest"])
+        .arg(dir.join("grd.toml"));
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn sync_with_unguessable_website_fails_code_3() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-sync-website", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let manifest = dir.join("grd.toml");
+    std::fs::write(
+        &manifest,
+        "[tools.tool]\nrepository = \"git.example.com/owner/tool\"\nasset-pattern = \"linux\"\n",
+    )?;
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["sync", "--dry-run", "--manifest"])
+        .arg(&manifest)
+        .arg("--state-dir")
+        .arg(dir.join("state"));
+
+    cmd.assert()
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("failed to guess website type"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn sync_keeps_tools_outside_of_its_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-sync-outside", std::process::id()));
+    let receipts_dir = dir.join("state").join("receipts");
+    std::fs::create_dir_all(&receipts_dir)?;
+    let manifest = dir.join("grd.toml");
+    std::fs::write(&manifest, "")?;
+    for (name, tools_dir, bin_dir) in [("inside", "tools", "bin"), ("outside", "opt", "opt-bin")] {
+        let receipt = serde_json::json!({
+            "name": name,
+            "repository": format!("github.com/owner/{name}"),
+            "website": "github",
+            "asset_pattern": "linux",
+            "tag": "v1",
+            "asset": "tool-linux",
+            "url": "https://example.com/tool-linux",
+            "sha256": "",
+            "installed_at": "2024-01-01T00:00:00Z",
+            "files": [dir.join(bin_dir).join(name)],
+            "tool_dir": dir.join(tools_dir).join(name),
+        });
+        std::fs::write(
+            receipts_dir.join(format!("{name}.json")),
+            receipt.to_string(),
+        )?;
+    }
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["sync", "--manifest"])
+        .arg(&manifest)
+        .arg("--bin-dir")
+        .arg(dir.join("bin"))
+        .arg("--tools-dir")
+        .arg(dir.join("tools"))
+        .arg("--state-dir")
+        .arg(dir.join("state"));
+
+    cmd.assert().success().stdout("inside\tv1\t-\n");
+    assert!(!receipts_dir.join("inside.json").exists());
+    assert!(receipts_dir.join("outside.json").exists());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn sync_locked_without_lockfile_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-sync-locked", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let manifest = dir.join("grd.toml");
+    std::fs::write(&manifest, "")?;
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args(["sync", "--locked", "--manifest"])
+        .arg(&manifest)
+        .arg("--state-dir")
+        .arg(dir.join("state"));
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("grd lock"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn missing_config_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "--config",
+        "does-not-exist.toml",
+        "github.com/owner/repo",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("Invalid configuration"));
+    Ok(())
+}
+
+#[test]
+fn invalid_config_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-config", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let config = dir.join("config.toml");
+    std::fs::write(
+        &config,
+        "[hosts.\"codeberg.org\"]\nwebsite-type = \"forgejo\"\n",
+    )?;
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.arg("--config")
+        .arg(&config)
+        .args(["query", "releases", "codeberg.org/owner/repo"]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("website-type"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn invalid_default_config_does_not_break_help_and_version() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-broken-config", std::process::id()));
+    std::fs::create_dir_all(dir.join("grd"))?;
+    std::fs::write(dir.join("grd/config.toml"), "broken [")?;
+
+    for arg in ["--help", "-V"] {
+        let mut cmd = Command::cargo_bin("grd")?;
+        cmd.env("XDG_CONFIG_HOME", &dir).arg(arg);
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.env("XDG_CONFIG_HOME", &dir).args(["list"]);
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("Invalid configuration"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn failing_token_command_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("grd-cli-{}-token", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let config = dir.join("config.toml");
+    std::fs::write(
+        &config,
+        "[hosts.\"codeberg.org\"]\nwebsite-type = \"gitea\"\ntoken = { command = \"exit 1\" }\n",
+    )?;
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.arg("--config")
+        .arg(&config)
+        .args(["query", "releases", "codeberg.org/owner/repo"]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("Invalid configuration"))
+        .stderr(predicate::str::contains("token"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn header_from_env_is_split_at_newlines() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "github.com/cm-auto/gitweb-release-downloader",
+    ])
+    .env("GRD_HEADER", "X-Valid: 1\nno-colon-here");
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("\"no-colon-here\""));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_env_value_fails_code_2() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "github.com/cm-auto/gitweb-release-downloader",
+    ])
+    .env("GRD_IP_TYPE", "ipv5");
+
+    cmd.assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("ipv5"));
+
+    // passed arguments take precedence over the environment
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "--ip-type",
+        "ipv4",
+        "--header",
+        "no-colon-here",
+    ])
+    .env("GRD_IP_TYPE", "ipv5");
+
+    cmd.assert().failure().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_tag_pattern_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "download",
+        "github.com/cm-auto/gitweb-release-downloader",
+        ".*",
+        "--tag-pattern",
+        "cli-(",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("RegEx"));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_version_requirement_fails_code_4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("grd")?;
+    cmd.args([
+        "query",
+        "releases",
+        "github.com/cm-auto/gitweb-release-downloader",
+        "--version",
+        "1.4 or 2",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("version requirement"));
+
+    Ok(())
+}